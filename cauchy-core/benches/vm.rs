@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::future::lazy;
+use futures::sync::{mpsc, oneshot};
+use futures::Stream;
+use tokio::runtime::Runtime;
+
+use cauchy_core::{
+    crypto::hashes::Identifiable,
+    db::{mongodb::MongoDB, Database},
+    primitives::transaction::Transaction,
+    utils::constants::MachineFlavour,
+    vm::{performance::Performance, Mailbox, VM},
+};
+
+const SCRIPTS: [&str; 2] = ["sha256", "ecdsa"];
+
+fn load_script(name: &str) -> Bytes {
+    let mut file = File::open(format!("src/tests/vm/scripts/{}", name)).unwrap();
+    let mut script = Vec::new();
+    file.read_to_end(&mut script).unwrap();
+    Bytes::from(script)
+}
+
+fn run_script(runtime: &mut Runtime, db: &MongoDB, flavour: MachineFlavour, script: &Bytes) {
+    let vm = VM::with_flavour(db.clone(), flavour);
+    let tx = Transaction::new(407548800, Bytes::from(&b"aux"[..]), script.clone());
+
+    // Complete the branch of every message sent
+    let (outbox, outbox_recv) = mpsc::channel(512);
+    let (mailbox, _) = Mailbox::new(outbox);
+    runtime
+        .spawn(outbox_recv.for_each(|(_, parent_branch)| parent_branch.send(()).map_err(|_| ())));
+
+    // Dummy terminator for root
+    let (parent_branch, _) = oneshot::channel();
+    runtime
+        .block_on(lazy(move || {
+            vm.run(
+                mailbox,
+                tx.clone(),
+                tx.get_id(),
+                Arc::new(Mutex::new(Performance::default())),
+                parent_branch,
+            )
+            .map(|_| ())
+            .map_err(|_| ())
+        }))
+        .ok();
+}
+
+fn vm_benchmark(c: &mut Criterion) {
+    let db = MongoDB::open_db("bench_vm").unwrap();
+    let mut runtime = Runtime::new().unwrap();
+
+    let mut flavours = vec![MachineFlavour::Sparse, MachineFlavour::Flat];
+    if cfg!(all(
+        feature = "asm",
+        target_arch = "x86_64",
+        target_os = "linux"
+    )) {
+        flavours.push(MachineFlavour::Asm);
+    }
+
+    for name in SCRIPTS.iter() {
+        let script = load_script(name);
+        for flavour in flavours.iter() {
+            c.bench_function(&format!("{}/{}", name, flavour.to_str()), |b| {
+                b.iter(|| run_script(&mut runtime, &db, *flavour, &script))
+            });
+        }
+    }
+}
+
+criterion_group!(benches, vm_benchmark);
+criterion_main!(benches);
@@ -1,12 +1,13 @@
 use bytes::Bytes;
 use failure::Error;
+use secp256k1::ecdh::SharedSecret;
 use secp256k1::key::{PublicKey, SecretKey, ONE_KEY};
 use secp256k1::rand::OsRng;
 use secp256k1::{Message, Secp256k1, Signature};
 
 use crate::{
     crypto::hashes::blake2b::*,
-    utils::errors::{InvalidPubkey, InvalidSignature},
+    utils::errors::{InvalidPubkey, InvalidSeckey, InvalidSignature},
 };
 
 pub fn generate_keypair() -> (SecretKey, PublicKey) {
@@ -38,6 +39,27 @@ pub fn pubkey_from_bytes(raw: Bytes) -> Result<PublicKey, Error> {
     }
 }
 
+pub fn pubkey_from_seckey(sk: &SecretKey) -> PublicKey {
+    let secp = Secp256k1::signing_only();
+    PublicKey::from_secret_key(&secp, sk)
+}
+
+pub fn bytes_from_seckey(key: &SecretKey) -> Bytes {
+    Bytes::from(&key[..])
+}
+
+pub fn seckey_from_bytes(raw: Bytes) -> Result<SecretKey, Error> {
+    match SecretKey::from_slice(&raw) {
+        Ok(some) => Ok(some),
+        Err(_) => Err(InvalidSeckey.into()),
+    }
+}
+
+// Diffie-Hellman secret shared between the holders of each keypair
+pub fn shared_secret(pk: &PublicKey, sk: &SecretKey) -> Bytes {
+    Bytes::from(&SharedSecret::new(pk, sk)[..])
+}
+
 pub fn bytes_from_sig(sig: Signature) -> Bytes {
     Bytes::from(&sig.serialize_compact()[..])
 }
@@ -1,18 +1,32 @@
+use std::collections::HashSet;
+use std::io;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 
+use bytes::Bytes;
 use failure::Error;
 use futures::{sync::mpsc, Future};
 use log::{error, info, warn};
+use stream_cancel::StreamExt;
 use tokio::codec::Framed;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::prelude::*;
 
 use crate::{
-    crypto::sketches::{odd_sketch::*, *},
-    db::{mongodb::MongoDB, storing::Storable},
+    crypto::{
+        hashes::Identifiable,
+        sketches::{odd_sketch::*, *},
+    },
+    db::{blocking, mongodb::MongoDB, storing::Storable},
     ego::{ego::*, peer_ego::*},
-    net::{heartbeats::*, messages::*},
+    net::{
+        addresses::normalise,
+        bans::Misbehaviour,
+        heartbeats::*,
+        messages::*,
+        relay::{batch_transactions, fetch_stored, unstored},
+        transport::handshake,
+    },
     primitives::{
         arena::Arena,
         status::{PeerStatus, Status},
@@ -24,8 +38,12 @@ use crate::{
         constants::*,
         errors::{DaemonError, ImpulseReceiveError},
     },
+    vm::validation::{validate_timestamp, validate_transaction},
 };
 
+// Standard batches wait behind an ongoing reconciliation and are rejected if they
+// conflict with provisional state, Force batches preempt and roll it back
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Priority {
     Force,
     Standard,
@@ -34,6 +52,28 @@ pub enum Priority {
 pub enum Origin {
     Peer(Arc<Mutex<PeerEgo>>),
     RPC,
+    Mempool,
+}
+
+// A reconciliation payload is served whole or not at all
+fn fetch_payload(db: &MongoDB, ids: HashSet<Bytes>) -> Option<Vec<Transaction>> {
+    let mut tx_pool = TxPool::with_capacity(ids.len());
+    for id in ids {
+        match Transaction::from_db(&mut db.clone(), id.clone()) {
+            Ok(Some(tx)) => {
+                tx_pool.insert(tx, Some(id.clone()), None);
+            }
+            Err(err) => {
+                error!(target: "daemon_event", "database error {:?}", err);
+                return None;
+            }
+            Ok(None) => {
+                error!(target: "daemon_event", "transaction {:?} not found", id);
+                return None;
+            }
+        }
+    }
+    Some(tx_pool.into_sorted_txs())
 }
 
 pub fn server(
@@ -47,292 +87,606 @@ pub fn server(
     info!(target: "daemon_event", "spawning deamon");
 
     // Bind socket
-    let addr = format!("0.0.0.0:{}", CONFIG.network.server_port).to_string();
+    // Dual stack
+    let addr = format!("[::]:{}", CONFIG.network.server_port).to_string();
     let addr = addr.parse::<SocketAddr>().unwrap();
     let listener = TcpListener::bind(&addr)
         .map_err(|_| DaemonError::BindFailure)
         .unwrap();
 
+    // Tag sockets with whether we dialed them
     let incoming = listener
         .incoming()
+        .map(|socket| (socket, false))
         .map_err(|err| Error::from(DaemonError::SocketAcceptanceFailure { err }))
-        .select(socket_recv.map_err(|_err| Error::from(DaemonError::Unreachable)))
+        .select(
+            socket_recv
+                .map(|socket| (socket, true))
+                .map_err(|_err| Error::from(DaemonError::Unreachable)),
+        )
         .map_err(|e| error!(target: "daemon_event", "error accepting socket; error = {:?}", e));
 
-    let server = incoming.for_each(move |socket| {
-        let socket_addr = socket.peer_addr().unwrap();
+    let server = incoming.for_each(move |(socket, outbound)| {
+        let socket_addr = normalise(socket.peer_addr().unwrap());
         info!(target: "daemon_event", "new server socket to {}", socket_addr);
 
-        // Construct peer ego
-        let (peer_ego, peer_stream) = PeerEgo::new();
-
-        // Send handshake
-        peer_ego.send_msg(Message::StartHandshake {
-            secret: peer_ego.get_secret(),
-        });
-
-        let arc_peer_ego = Arc::new(Mutex::new(peer_ego));
+        // Refuse banned peers, and inbound peers beyond the limit
         let mut arena_guard = arena.lock().unwrap();
-        arena_guard.new_peer(&socket_addr, arc_peer_ego.clone());
+        if arena_guard.is_banned(&socket_addr) {
+            info!(target: "daemon_event", "refusing banned peer {}", socket_addr);
+            if outbound {
+                arena_guard.dial_failed(socket_addr);
+            }
+            return tokio::spawn(future::ok(()));
+        }
+        if !outbound && arena_guard.n_inbound() >= CONFIG.connections.max_inbound {
+            info!(target: "daemon_event", "refusing {}, inbound limit reached", socket_addr);
+            return tokio::spawn(future::ok(()));
+        }
+        if !outbound {
+            arena_guard.start_handshake();
+        }
         drop(arena_guard);
 
-        // Frame the socket
-        let framed_sock = Framed::new(socket, MessageCodec);
-        let (send_stream, received_stream) = framed_sock.split();
+        // Encrypt the connection before any message is exchanged
+        let tx_db = tx_db.clone();
+        let ego = ego.clone();
+        let send_reconcile = send_reconcile.clone();
+        let mempool = mempool.clone();
+        let arena = arena.clone();
+        let arena_inner = arena.clone();
+        let arena_handshake = arena.clone();
+        let connection = handshake(socket)
+            .then(move |res| {
+                if !outbound {
+                    arena_handshake.lock().unwrap().end_handshake();
+                }
+                res
+            })
+            .map_err(move |err| {
+                error!(target: "daemon_event", "transport handshake with {} failed: {}", socket_addr, err);
+                if outbound {
+                    arena_inner.lock().unwrap().dial_failed(socket_addr);
+                }
+            })
+            .and_then(move |(socket, session)| {
+                // Construct peer ego
+                let (peer_ego, peer_stream) = PeerEgo::new(session.id);
+
+                let session_id = peer_ego.get_session_id();
+                let arc_peer_ego = Arc::new(Mutex::new(peer_ego));
+                let mut arena_guard = arena.lock().unwrap();
+                arena_guard.new_peer(&socket_addr, arc_peer_ego.clone(), outbound);
+                drop(arena_guard);
+
+                // Frame the socket, decoding for the version the peer announces
+                let negotiated = session.codec.get_version();
+                let framed_sock = Framed::new(socket, session.codec);
+                let (send_stream, received_stream) = framed_sock.split();
+
+                // Malformed messages end the connection
+                let arc_peer_ego_inner = arc_peer_ego.clone();
+                let received_stream = received_stream.map_err(move |err| {
+                    if err.downcast_ref::<io::Error>().is_none() {
+                        error!(target: "daemon_event", "malformed message from {}", socket_addr);
+                        arc_peer_ego_inner
+                            .lock()
+                            .unwrap()
+                            .penalise(Misbehaviour::MalformedMessage);
+                    }
+                    err
+                });
+
+                // Any message shows the peer is alive, including from peers unable to answer pings
+                let arc_peer_ego_inner = arc_peer_ego.clone();
+                let received_stream = received_stream
+                    .inspect(move |_| arc_peer_ego_inner.lock().unwrap().mark_seen());
+
+                // Only the handshake is accepted from peers yet to prove their key
+                let arc_peer_ego_inner = arc_peer_ego.clone();
+                let received_stream = received_stream.filter(move |msg| match msg {
+                    Message::Version { .. } | Message::StartHandshake { .. } | Message::EndHandshake { .. } => true,
+                    _ => {
+                        let identified = arc_peer_ego_inner.lock().unwrap().get_pubkey().is_some();
+                        if !identified {
+                            error!(target: "daemon_event", "dropping message from {} before handshake", socket_addr);
+                        }
+                        identified
+                    }
+                });
+
+                // Resolves once the peer is to be disconnected
+                let tripwire = arc_peer_ego.lock().unwrap().get_tripwire();
+
+                // Filter through received messages
+                let tx_db_inner = tx_db.clone();
+                let ego_inner = ego.clone();
+                let send_reconcile_inner = send_reconcile.clone();
+                let mempool_inner = mempool.clone();
+                let arena_inner = arena.clone();
+                let response_stream = received_stream.filter_map(move |msg| match msg {
+                    Message::Version { version, features } => {
+                        info!(target: "daemon_event", "received version {} from {}", version, socket_addr);
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        if peer_ego_guard.get_version().is_some() {
+                            error!(target: "daemon_event", "received repeated version from {}", socket_addr);
+                            return None;
+                        }
+                        if version < MIN_PROTOCOL_VERSION {
+                            info!(target: "daemon_event", "disconnecting {}, unsupported version {}", socket_addr, version);
+                            peer_ego_guard.disconnect();
+                            return None;
+                        }
+                        peer_ego_guard.set_version(version, features);
+                        negotiated.set(version.min(PROTOCOL_VERSION));
 
-        // Filter through received messages
-        let tx_db_inner = tx_db.clone();
-        let ego_inner = ego.clone();
-        let send_reconcile_inner = send_reconcile.clone();
-        let mempool_inner = mempool.clone();
-        let response_stream = received_stream.filter_map(move |msg| match msg {
-            Message::StartHandshake { secret } => {
-                info!(target: "daemon_event", "received handshake initialisation from {}", socket_addr);
+                        // Send handshake
+                        Some(Message::StartHandshake {
+                            secret: peer_ego_guard.get_secret(),
+                        })
+                    }
+                    Message::StartHandshake { secret } => {
+                        info!(target: "daemon_event", "received handshake initialisation from {}", socket_addr);
+                        if arc_peer_ego.lock().unwrap().get_version().is_none() {
+                            error!(target: "daemon_event", "received handshake from {} before version", socket_addr);
+                            arc_peer_ego.lock().unwrap().disconnect();
+                            return None;
+                        }
 
-                Some(ego_inner.lock().unwrap().generate_end_handshake(secret))
-            }
-            Message::EndHandshake { pubkey, sig } => {
-                info!(target: "daemon_event", "received handshake finalisation from {}", socket_addr);
+                        Some(ego_inner.lock().unwrap().generate_end_handshake(secret, &session_id))
+                    }
+                    Message::EndHandshake { pubkey, sig } => {
+                        info!(target: "daemon_event", "received handshake finalisation from {}", socket_addr);
+
+                        // Refuse banned keys
+                        let mut arena_guard = arena_inner.lock().unwrap();
+                        if arena_guard.is_banned_pubkey(&pubkey) {
+                            info!(target: "daemon_event", "refusing banned key from {}", socket_addr);
+                            arc_peer_ego.lock().unwrap().disconnect();
+                            return None;
+                        }
+                        let own_pubkey = ego_inner.lock().unwrap().get_pubkey();
+
+                        // If peer correctly signs our secret we upgrade them from a dummy pk
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        peer_ego_guard.check_handshake(&sig, &pubkey);
+                        if peer_ego_guard.get_pubkey().is_none() {
+                            error!(target: "daemon_event", "invalid handshake from {}", socket_addr);
+                            peer_ego_guard.penalise(Misbehaviour::InvalidHandshake);
+                            peer_ego_guard.disconnect();
+                            return None;
+                        }
 
-                // If peer correctly signs our secret we upgrade them from a dummy pk
-                arc_peer_ego.lock().unwrap().check_handshake(&sig, &pubkey);
-                None
-            }
-            Message::Work(work_stack) => {
-                info!(target: "daemon_event", "received work from {}", socket_addr);
-
-                // Lock peer ego
-                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
-                if peer_ego_guard.get_status() == PeerStatus::WorkPull {
-                    // Update work
-                    peer_ego_guard.update_status(PeerStatus::Fighting(work_stack));
-                } else {
-                    // TODO: Ban here
-                    error!(target: "daemon_event", "received work from non-pull target")
-                }
+                        // Misbehaviour under the same key carries over
+                        peer_ego_guard.raise_score(arena_guard.get_score(&socket_addr, Some(&pubkey)));
+                        if peer_ego_guard.is_banned() {
+                            return None;
+                        }
 
-                None
-            }
-            Message::MiniSketch { minisketch } => {
-                info!(target: "daemon_event", "received minisketch from {}", socket_addr);
+                        // Drop connections to self
+                        if pubkey == own_pubkey {
+                            info!(target: "daemon_event", "connected to self at {}", socket_addr);
+                            arena_guard.forget_address(&socket_addr);
+                            peer_ego_guard.disconnect();
+                            return None;
+                        }
 
-                // Lock peer ego
-                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        // Addresses we dialed are known to accept connections
+                        if outbound {
+                            arena_guard.record_connection(socket_addr, pubkey);
+                        }
 
-                // TODO: This can be done properly
-                let perceived_oddsketch = peer_ego_guard.get_perceived_oddsketch();
-                let perceived_minisketch = peer_ego_guard.get_perceived_minisketch();
+                        // Discover peers
+                        peer_ego_guard.request_peers();
+                        Some(Message::GetPeers)
+                    }
+                    Message::Work(work_stack) => {
+                        info!(target: "daemon_event", "received work from {}", socket_addr);
+
+                        // Lock peer ego
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        if peer_ego_guard.get_status() == PeerStatus::WorkPull {
+                            // Update work
+                            peer_ego_guard.update_status(PeerStatus::Fighting(work_stack));
+                        } else {
+                            error!(target: "daemon_event", "received work from non-pull target");
+                            peer_ego_guard.penalise(Misbehaviour::UnsolicitedWork);
+                        }
+
+                        None
+                    }
+                    Message::MiniSketch { minisketch } => {
+                        info!(target: "daemon_event", "received minisketch from {}", socket_addr);
+
+                        // Lock peer ego
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+
+                        // TODO: This can be done properly
+                        let perceived_oddsketch = peer_ego_guard.get_perceived_oddsketch();
+                        let perceived_minisketch = peer_ego_guard.get_perceived_minisketch();
+
+                        // Only respond to reconciliation target
+                        match peer_ego_guard.get_status_mut() {
+                            PeerStatus::StatePull(expectation) => {
+                                match (perceived_minisketch, perceived_oddsketch) {
+                                    (Some(perceived_minisketch), Some(perceived_oddsketch)) => {
+                                        let peer_oddsketch = expectation.get_oddsketch();
+
+                                        // Decode difference
+                                        let (excess_actor_ids, missing_actor_ids) = (perceived_minisketch
+                                            - minisketch.clone())
+                                        .decode()
+                                        .unwrap();
+
+                                        info!(
+                                            target: "daemon_event", 
+                                            "minisketch decode reveals excess {} and mising {}",
+                                            excess_actor_ids.len(),
+                                            missing_actor_ids.len()
+                                        );
+
+                                        // Check for fraud
+                                        if OddSketch::sketch_ids(&excess_actor_ids)
+                                            .xor(&OddSketch::sketch_ids(&missing_actor_ids))
+                                            == perceived_oddsketch.xor(&peer_oddsketch)
+                                        {
+                                            info!(target: "daemon_event", "minisketch passed validation");
+
+                                            // Set expected IDs
+                                            expectation.update_ids(missing_actor_ids.clone());
+
+                                            // Set expected minisketch
+                                            expectation.update_minisketch(minisketch);
+
+                                            Some(Message::GetTransactions {
+                                                ids: missing_actor_ids,
+                                            })
+                                        } else {
+                                            error!(target: "daemon_event", "fraudulent minisketch from {}", socket_addr);
+                                            peer_ego_guard.penalise(Misbehaviour::FraudulentMiniSketch);
+
+                                            // Stop reconciliation
+                                            peer_ego_guard.update_status(PeerStatus::Idle);
+                                            None
+                                        }
+                                    }
+                                    _ => {
+                                        // TODO: More matches
+                                        error!(
+                                            target: "daemon_event", 
+                                            "received minisketch from {} while not pulling state",
+                                            socket_addr
+                                        );
+                                        peer_ego_guard.penalise(Misbehaviour::UnsolicitedMiniSketch);
+                                        peer_ego_guard.update_status(PeerStatus::Idle);
+                                        None
+                                    }
+                                }
+                            }
+                            _ => {
+                                error!(
+                                    target: "daemon_event",
+                                    "received minisketch from non-pull target {}",
+                                    socket_addr
+                                );
+                                peer_ego_guard.penalise(Misbehaviour::UnsolicitedMiniSketch);
+                                None
+                            }
+                        }
+                    }
+                    Message::GetTransactions { ids } => {
+                        // TODO: Check if reconcilee?
+                        info!(target: "daemon_event", "received transaction request from {}", socket_addr);
+
+                        // Lock peer ego
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+
+                        // Relay requests are served from stored transactions, each sent at most once
+                        if peer_ego_guard.get_status() != PeerStatus::StatePush {
+                            if ids.len() > MAX_INVENTORY_PER_MSG {
+                                error!(target: "daemon_event", "received oversized transaction request from {}", socket_addr);
+                                peer_ego_guard.penalise(Misbehaviour::MalformedMessage);
+                                return None;
+                            }
+                            let ids: Vec<Bytes> = ids.into_iter().filter(|id| !peer_ego_guard.was_sent(id)).collect();
+                            drop(peer_ego_guard);
+                            if ids.is_empty() {
+                                return None;
+                            }
 
-                // Only respond to reconciliation target
-                match peer_ego_guard.get_status_mut() {
-                    PeerStatus::StatePull(expectation) => {
-                        match (perceived_minisketch, perceived_oddsketch) {
-                            (Some(perceived_minisketch), Some(perceived_oddsketch)) => {
-                                let peer_oddsketch = expectation.get_oddsketch();
+                            // Looked up off the executor, then sent on the peer sink
+                            let tx_db = tx_db_inner.clone();
+                            let arc_peer_ego = arc_peer_ego.clone();
+                            tokio::spawn(blocking(move || fetch_stored(&tx_db, ids)).map(move |found| {
+                                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                                let mut txs = vec![];
+                                for (id, tx) in found {
+                                    if !peer_ego_guard.was_sent(&id) {
+                                        peer_ego_guard.mark_sent(id);
+                                        txs.push(tx);
+                                    }
+                                }
+                                info!(target: "daemon_event", "relaying {} transactions to {}", txs.len(), socket_addr);
+                                for txs in batch_transactions(txs) {
+                                    peer_ego_guard.send_msg(Message::Transactions { txs });
+                                }
+                            }));
+                            return None;
+                        }
 
-                                // Decode difference
-                                let (excess_actor_ids, missing_actor_ids) = (perceived_minisketch
-                                    - minisketch.clone())
-                                .decode()
-                                .unwrap();
+                        drop(peer_ego_guard);
 
+                        // Reconciliation payloads are looked up off the executor too
+                        let tx_db = tx_db_inner.clone();
+                        let arc_peer_ego = arc_peer_ego.clone();
+                        tokio::spawn(blocking(move || fetch_payload(&tx_db, ids)).map(move |txs| {
+                            let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                            peer_ego_guard.update_status(PeerStatus::Idle);
+                            if let Some(txs) = txs {
+                                // Send transactions
                                 info!(
                                     target: "daemon_event", 
-                                    "minisketch decode reveals excess {} and mising {}",
-                                    excess_actor_ids.len(),
-                                    missing_actor_ids.len()
+                                    "replying to {} with {} transactions",
+                                    socket_addr,
+                                    txs.len()
                                 );
+                                peer_ego_guard.send_msg(Message::Transactions { txs });
+                            }
+                        }));
+                        None
+                    }
+                    Message::Transactions { txs } => {
+                        info!(target: "daemon_event", "received transactions from {}", socket_addr);
+
+                        // Lock peer ego
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+
+                        // Replies to relay requests go to the mempool, unless they are the reconciliation payload
+                        let ids: Vec<Bytes> = txs.iter().map(|tx| tx.get_id()).collect();
+                        let reconciling = match peer_ego_guard.get_status() {
+                            PeerStatus::StatePull(expectation) => expectation.is_expected_ids(&ids),
+                            _ => false,
+                        };
+                        let relayed = !reconciling && peer_ego_guard.fulfil_requests(&ids);
+                        peer_ego_guard.mark_announced(ids);
+
+                        // Admission checks
+                        let n_txs = txs.len();
+                        let txs: Vec<Transaction> = txs
+                            .into_iter()
+                            .filter(|tx| validate_transaction(tx).is_ok())
+                            .collect();
+                        let n_invalid = n_txs - txs.len();
+                        if n_invalid != 0 {
+                            error!(
+                                target: "daemon_event",
+                                "received {} invalid transactions from {}",
+                                n_invalid,
+                                socket_addr
+                            );
+                        }
+
+                        match peer_ego_guard.get_status() {
+                            PeerStatus::StatePull(_) if !relayed && n_invalid != 0 => {
+                                peer_ego_guard.penalise(Misbehaviour::InvalidTransactions);
+
+                                // Abandon reconciliation, releasing the peer before locking the ego
+                                peer_ego_guard.update_status(PeerStatus::Idle);
+                                drop(peer_ego_guard);
+                                ego_inner.lock().unwrap().update_status(Status::Idle);
+                            }
+                            PeerStatus::StatePull(expectation) if !relayed => {
+                                // Only a peer that beat our own distance may roll back our state
+                                let priority = if expectation.is_leader() {
+                                    Priority::Force
+                                } else {
+                                    Priority::Standard
+                                };
 
-                                // Check for fraud
-                                if OddSketch::sketch_ids(&excess_actor_ids)
-                                    .xor(&OddSketch::sketch_ids(&missing_actor_ids))
-                                    == perceived_oddsketch.xor(&peer_oddsketch)
+                                // Exempt from timeouts until ingest handles the payload
+                                if let PeerStatus::StatePull(expectation) =
+                                    peer_ego_guard.get_status_mut()
                                 {
-                                    info!(target: "daemon_event", "minisketch passed validation");
+                                    expectation.mark_received();
+                                }
 
-                                    // Set expected IDs
-                                    expectation.update_ids(missing_actor_ids.clone());
+                                // Send to back stage
+                                let mut tx_pool = TxPool::with_capacity(txs.len());
+                                tx_pool.insert_batch(txs, true); // TODO: Catch out-of-order
+                                drop(peer_ego_guard);
+
+                                tokio::spawn(
+                                    send_reconcile_inner
+                                        .clone()
+                                        .send((
+                                            Origin::Peer(arc_peer_ego.clone()),
+                                            tx_pool,
+                                            priority,
+                                        ))
+                                        .map_err(|_| ())
+                                        .and_then(|_| future::ok(())),
+                                );
+                            }
+                            PeerStatus::StatePush if !relayed => {
+                                error!(
+                                    target: "daemon_event", 
+                                    "received transactions from {} while pushing state",
+                                    socket_addr
+                                );
+                                peer_ego_guard.penalise(Misbehaviour::TransactionsWhilePushing);
+                            }
+                            _ => {
+                                if relayed && n_invalid != 0 {
+                                    peer_ego_guard.penalise(Misbehaviour::InvalidTransactions);
+                                }
 
-                                    // Set expected minisketch
-                                    expectation.update_minisketch(minisketch);
+                                // Admit only timely transactions, mempool defers those in the future
+                                let txs: Vec<Transaction> = txs
+                                    .into_iter()
+                                    .filter(|tx| validate_timestamp(tx).is_ok())
+                                    .collect();
+                                if txs.is_empty() {
+                                    return None;
+                                }
 
-                                    Some(Message::GetTransactions {
-                                        ids: missing_actor_ids,
-                                    })
-                                } else {
-                                    error!(target: "daemon_event", "fraudulent minisketch from {}", socket_addr);
-                                    // TODO: Ban here
-                                    // Stop reconciliation
-                                    peer_ego_guard.update_status(PeerStatus::Idle);
-                                    None
+                                // Mempool keeps its own order
+                                if let Err(err) = mempool_inner.lock().unwrap().insert_batch(txs, false) {
+                                    error!(target: "daemon_event", "failed to add to mempool: {}", err);
                                 }
                             }
+                        }
+
+                        None
+                    }
+                    Message::Reconcile => {
+                        info!(target: "daemon_event", "received reconcile from {}", socket_addr);
+
+                        // Lock peer ego
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+
+                        if peer_ego_guard.get_status() == PeerStatus::Idle {
+                            info!(target: "daemon_event", "replying to {} with minisketch", socket_addr);
+
+                            // Send minisketch
+                            // Set status of peer push
+                            peer_ego_guard.update_status(PeerStatus::StatePush);
+                            Some(Message::MiniSketch {
+                                minisketch: peer_ego_guard.get_perceived_minisketch()?,
+                            })
+                        } else {
+                            info!(target: "daemon_event", "replying to {} with work negack", socket_addr);
+                            Some(Message::ReconcileNegAck)
+                        }
+                    }
+                    Message::GetWork => {
+                        info!(target: "daemon_event", "received get work from {}", socket_addr);
+                        let ego_guard = ego_inner.lock().unwrap();
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        let work_stack = ego_guard.get_work_stack();
+                        peer_ego_guard.push_work(work_stack, ego_guard.get_minisketch());
+                        None
+                    }
+                    Message::ReconcileNegAck => {
+                        info!(target: "daemon_event", "received reconcile negack from {}", socket_addr);
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        match peer_ego_guard.get_status() {
+                            PeerStatus::StatePull(_) => peer_ego_guard.update_status(PeerStatus::Idle),
                             _ => {
-                                // TODO: Ban here
-                                // TODO: More matches
                                 error!(
                                     target: "daemon_event", 
-                                    "received minisketch from {} while not pulling state",
+                                    "received negack from {} while not pulling state",
                                     socket_addr
                                 );
-                                peer_ego_guard.update_status(PeerStatus::Idle);
-                                None
+                                peer_ego_guard.penalise(Misbehaviour::UnexpectedNegAck);
                             }
-                        }
+                        };
+                        None
                     }
-                    _ => None, // TODO: Ban here
-                }
-            }
-            Message::GetTransactions { ids } => {
-                // TODO: Check if reconcilee?
-                info!(target: "daemon_event", "received transaction request from {}", socket_addr);
-
-                // Lock peer ego
-                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
-
-                // Init tx pool
-                let mut tx_pool = TxPool::with_capacity(ids.len());
-
-                // Find transactions
-                for id in ids {
-                    // if CONFIG.debugging.daemon_verbose {
-                    //     println!("searching for transaction {:?}", id);
-                    // }
-                    match Transaction::from_db(&mut tx_db_inner.clone(), id.clone()) {
-                        Ok(Some(tx)) => {
-                            // if CONFIG.debugging.daemon_verbose {
-                            //     println!("Found {:?}", id);
-                            // }
-                            tx_pool.insert(tx, Some(id.clone()), None);
-                        }
-                        Err(err) => {
-                            error!(target: "daemon_event", "database error {:?}", err);
-                            peer_ego_guard.update_status(PeerStatus::Idle);
-                            return None;
+                    Message::GetPeers => {
+                        info!(target: "daemon_event", "received get peers from {}", socket_addr);
+                        let arena_guard = arena_inner.lock().unwrap();
+                        Some(Message::Peers {
+                            peers: arena_guard.sample_addresses(MAX_PEERS_PER_MSG),
+                        })
+                    }
+                    Message::Inventory { ids } => {
+                        info!(target: "daemon_event", "received inventory of {} from {}", ids.len(), socket_addr);
+                        let arena_guard = arena_inner.lock().unwrap();
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        let ids = peer_ego_guard.newly_announced(ids);
+
+                        // Reconciliation already covers the peer being reconciled with
+                        match peer_ego_guard.get_status() {
+                            PeerStatus::StatePull(_) | PeerStatus::StatePush => return None,
+                            _ => (),
                         }
-                        Ok(None) => {
-                            error!(target: "daemon_event", "transaction {:?} not found", id);
-                            peer_ego_guard.update_status(PeerStatus::Idle);
+
+                        // Look up only what could be requested, one lookup per peer at a time
+                        let room = peer_ego_guard.request_room();
+                        let ids: HashSet<Bytes> = ids
+                            .into_iter()
+                            .filter(|id| !arena_guard.is_requested(id))
+                            .take(room)
+                            .collect();
+                        if ids.is_empty() || !peer_ego_guard.start_lookup() {
                             return None;
                         }
-                    }
-                }
-                let txs = tx_pool.into_sorted_txs();
-                // Send transactions
-                info!(
-                    target: "daemon_event", 
-                    "replying to {} with {} transactions",
-                    socket_addr,
-                    txs.len()
-                );
-                peer_ego_guard.update_status(PeerStatus::Idle);
-                Some(Message::Transactions { txs })
-            }
-            Message::Transactions { txs } => {
-                info!(target: "daemon_event", "received transactions from {}", socket_addr);
-
-                // Lock peer ego
-                let peer_ego_guard = arc_peer_ego.lock().unwrap();
-
-                match peer_ego_guard.get_status() {
-                    PeerStatus::StatePull(expectation) => {
-                        // Send to back stage
-                        let mut tx_pool = TxPool::with_capacity(txs.len());
-                        tx_pool.insert_batch(txs, true); // TODO: Catch out-of-order
                         drop(peer_ego_guard);
-
-                        tokio::spawn(
-                            send_reconcile_inner
-                                .clone()
-                                .send((
-                                    Origin::Peer(arc_peer_ego.clone()),
-                                    tx_pool,
-                                    Priority::Standard,
-                                )) // TODO: Force vs Standard here
-                                .map_err(|_| ())
-                                .and_then(|_| future::ok(())),
-                        );
-
-                        // Finished pull
-                        ego_inner.lock().unwrap().update_status(Status::Idle);
+                        drop(arena_guard);
+
+                        // Request transactions not yet stored, looked up off the executor
+                        let tx_db = tx_db_inner.clone();
+                        let arena = arena_inner.clone();
+                        let arc_peer_ego = arc_peer_ego.clone();
+                        tokio::spawn(blocking(move || unstored(&tx_db, ids)).map(move |unknown| {
+                            // Reconciliation is only started under the arena lock
+                            let mut arena_guard = arena.lock().unwrap();
+                            let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                            peer_ego_guard.end_lookup();
+                            match peer_ego_guard.get_status() {
+                                PeerStatus::StatePull(_) | PeerStatus::StatePush => return,
+                                _ => (),
+                            }
+                            let room = peer_ego_guard.request_room();
+                            let ids = arena_guard.request_transactions(unknown.into_iter().take(room).collect());
+                            drop(arena_guard);
+                            if ids.is_empty() {
+                                return;
+                            }
+                            peer_ego_guard.add_requests(&ids);
+                            peer_ego_guard.send_msg(Message::GetTransactions { ids });
+                        }));
+                        None
                     }
-                    PeerStatus::StatePush => {
-                        // TODO: Ban here
-                        error!(
-                            target: "daemon_event", 
-                            "received transactions from {} while pushing state",
-                            socket_addr
-                        );
+                    Message::Ping { nonce } => Some(Message::Pong { nonce }),
+                    Message::Pong { nonce } => {
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        if !peer_ego_guard.pong(nonce) {
+                            error!(target: "daemon_event", "received unsolicited pong from {}", socket_addr);
+                            peer_ego_guard.penalise(Misbehaviour::UnsolicitedPong);
+                        }
+                        None
                     }
-                    _ => {
-                        mempool_inner.lock().unwrap().insert_batch(txs, true);
+                    Message::Peers { peers } => {
+                        info!(target: "daemon_event", "received peers from {}", socket_addr);
+                        let mut arena_guard = arena_inner.lock().unwrap();
+                        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                        if !peer_ego_guard.take_peers_request() {
+                            error!(target: "daemon_event", "received unsolicited peers from {}", socket_addr);
+                            peer_ego_guard.penalise(Misbehaviour::UnsolicitedPeers);
+                            return None;
+                        }
+                        drop(peer_ego_guard);
+                        arena_guard.learn_addresses(peers, &socket_addr);
+                        None
                     }
-                }
+                });
 
-                None
-            }
-            Message::Reconcile => {
-                info!(target: "daemon_event", "received reconcile from {}", socket_addr);
-
-                // Lock peer ego
-                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
-
-                if peer_ego_guard.get_status() == PeerStatus::Idle {
-                    info!(target: "daemon_event", "replying to {} with minisketch", socket_addr);
-
-                    // Send minisketch
-                    // Set status of peer push
-                    peer_ego_guard.update_status(PeerStatus::StatePush);
-                    Some(Message::MiniSketch {
-                        minisketch: peer_ego_guard.get_perceived_minisketch()?,
-                    })
-                } else {
-                    info!(target: "daemon_event", "replying to {} with work negack", socket_addr);
-                    Some(Message::ReconcileNegAck)
-                }
-            }
-            Message::GetWork => {
-                info!(target: "daemon_event", "received get work from {}", socket_addr);
-                let ego_guard = ego_inner.lock().unwrap();
-                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
-                let work_stack = ego_guard.get_work_stack();
-                peer_ego_guard.push_work(work_stack, ego_guard.get_minisketch());
-                None
-            }
-            Message::ReconcileNegAck => {
-                info!(target: "daemon_event", "received reconcile negack from {}", socket_addr);
-                let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
-                match peer_ego_guard.get_status() {
-                    PeerStatus::StatePull(_) => peer_ego_guard.update_status(PeerStatus::Idle),
-                    _ => {
-                        // TODO: Misbehaviour
-                        error!(
-                            target: "daemon_event", 
-                            "received negack from {} while not pulling state",
-                            socket_addr
-                        );
-                    }
+                // Remove failed responses and merge with heartbeats, until disconnected
+                let out_stream = response_stream
+                    .select(peer_stream.map_err(|_| ImpulseReceiveError.into()))
+                    .take_until(tripwire);
+
+                // Announce version before anything else
+                let version = Message::Version {
+                    version: PROTOCOL_VERSION,
+                    features: LOCAL_FEATURES,
                 };
-                None
-            }
-            Message::Peers { peers } => unreachable!(),
-        });
+                let out_stream = stream::once(Ok(version)).chain(out_stream);
 
-        // Remove failed responses and merge with heartbeats
-        let out_stream =
-            response_stream.select(peer_stream.map_err(|_| ImpulseReceiveError.into()));
+                // Send responses
+                let arena_inner = arena.clone();
+                send_stream.send_all(out_stream).then(move |res| {
+                    if let Err(e) = res {
+                        error!(target: "daemon_event", "socket error {:?}", e);
+                    }
 
-        // Send responses
-        let arena_inner = arena.clone();
-        let send = send_stream
-            .send_all(out_stream)
-            .map(|_| ())
-            .or_else(move |e| {
-                error!(target: "daemon_event", "socket error {:?}", e);
-                arena_inner.lock().unwrap().remove_peer(&socket_addr);
-                Ok(())
+                    // Ban misbehaving peers
+                    arena_inner.lock().unwrap().remove_peer(&socket_addr);
+                    Ok(())
+                })
             });
-        tokio::spawn(send)
+        tokio::spawn(connection)
     });
     server
 }
@@ -4,10 +4,15 @@ pub mod mongodb;
 pub mod storing;
 
 use failure::Error;
+use futures::future::poll_fn;
+use futures::{Async, Future};
 
 pub enum DataType {
     TX,
     State,
+    Performance,
+    Event,
+    Address,
 }
 
 pub trait Database<DB> {
@@ -18,12 +23,45 @@ pub trait Database<DB> {
         dtype: &DataType,
         doc: bson::ordered::OrderedDocument,
     ) -> Result<Option<bson::ordered::OrderedDocument>, Error>;
+    fn get_many(
+        &self,
+        dtype: &DataType,
+        doc: bson::ordered::OrderedDocument,
+        skip: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<bson::ordered::OrderedDocument>, Error>;
     fn update(
         &self,
         dtype: &DataType,
         filter: bson::ordered::OrderedDocument,
         update: bson::ordered::OrderedDocument,
     ) -> Result<(i32), Error>;
+    fn upsert(
+        &self,
+        dtype: &DataType,
+        filter: bson::ordered::OrderedDocument,
+        doc: bson::ordered::OrderedDocument,
+    ) -> Result<(), Error>;
+    fn remove(
+        &self,
+        dtype: &DataType,
+        filter: bson::ordered::OrderedDocument,
+    ) -> Result<i32, Error>;
+}
+
+// Run blocking database work without stalling the executor
+pub fn blocking<F, T>(f: F) -> impl Future<Item = T, Error = ()>
+where
+    F: FnOnce() -> T,
+{
+    let mut f = Some(f);
+    poll_fn(move || {
+        match tokio_threadpool::blocking(|| (f.take().unwrap())()) {
+            Ok(ready) => Ok(ready),
+            // Not on a threadpool, run inline
+            Err(_) => Ok(Async::Ready((f.take().unwrap())())),
+        }
+    })
 }
 
 impl DataType {
@@ -31,6 +69,9 @@ impl DataType {
         match *self {
             DataType::TX => "txs",
             DataType::State => "states",
+            DataType::Performance => "performances",
+            DataType::Event => "events",
+            DataType::Address => "addresses",
         }
     }
 }
@@ -36,6 +36,29 @@ impl Database<MongoDB> for MongoDB {
         }
     }
 
+    // Returns matches ordered by id, skipping the first `skip`
+    fn get_many(
+        &self,
+        dtype: &DataType,
+        doc: bson::ordered::OrderedDocument,
+        skip: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<bson::ordered::OrderedDocument>, Error> {
+        let mut fo = mongodb::coll::options::FindOptions::new();
+        fo.sort = Some(doc! { "_id" : 1 });
+        fo.skip = Some(skip as i64);
+        fo.limit = limit.map(|limit| limit as i64);
+        let cursor = self
+            .0
+            .collection(dtype.as_str())
+            .find(Some(doc), Some(fo))?;
+        let mut found_docs = vec![];
+        for found_doc in cursor {
+            found_docs.push(found_doc?);
+        }
+        Ok(found_docs)
+    }
+
     // TODO: Handle unhappy path
     fn put(&self, dtype: &DataType, doc: bson::ordered::OrderedDocument) -> Result<(), Error> {
         self.0
@@ -60,6 +83,35 @@ impl Database<MongoDB> for MongoDB {
             .modified_count;
         Ok(n)
     }
+
+    // Replaces the matching document, inserting if there is none
+    fn upsert(
+        &self,
+        dtype: &DataType,
+        filter: bson::ordered::OrderedDocument,
+        doc: bson::ordered::OrderedDocument,
+    ) -> Result<(), Error> {
+        let mut ro = mongodb::coll::options::ReplaceOptions::new();
+        ro.upsert = Some(true);
+        self.0
+            .collection(dtype.as_str())
+            .replace_one(filter, doc, Some(ro))?;
+        Ok(())
+    }
+
+    // Returns the number of documents removed
+    fn remove(
+        &self,
+        dtype: &DataType,
+        filter: bson::ordered::OrderedDocument,
+    ) -> Result<i32, Error> {
+        let n = self
+            .0
+            .collection(dtype.as_str())
+            .delete_many(filter, None)?
+            .deleted_count;
+        Ok(n)
+    }
 }
 
 #[cfg(test)]
@@ -1,9 +1,16 @@
 use std::convert::TryFrom;
+use std::net::SocketAddr;
 
 use bytes::Bytes;
 use failure::Error;
 
-use crate::{crypto::hashes::*, primitives::transaction::*, vm::session::Session};
+use crate::{
+    crypto::{hashes::*, signatures::ecdsa::*},
+    net::{addresses::AddressRecord, peers::Peer},
+    primitives::{act::Event, transaction::*},
+    utils::constants::MAX_EVENTS_PER_FETCH,
+    vm::{performance::Performance, session::Session},
+};
 
 use super::{mongodb::MongoDB, DataType, Database};
 use bson::spec::BinarySubtype;
@@ -45,6 +52,189 @@ impl Storable for Transaction {
     }
 }
 
+impl Storable for Performance {
+    type Context = MongoDB;
+    fn from_db(db: &mut MongoDB, perf_id: Bytes) -> Result<Option<Performance>, Error> {
+        match db.get(
+            &DataType::Performance,
+            doc! { "_id" =>  Bson::Binary(BinarySubtype::Generic, perf_id.to_vec())},
+        ) {
+            Ok(Some(some)) => {
+                let perf_data = some.get_binary_generic("v").unwrap();
+                let performance = Self::try_from(Bytes::from(perf_data.to_vec()))?;
+                Ok(Some(performance))
+            }
+            Ok(None) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn to_db(&self, db: &mut MongoDB, key: Option<Bytes>) -> Result<(), Error> {
+        let perf_id = match key {
+            Some(some) => some,
+            None => self.get_id(),
+        };
+
+        // Index events, keyed by performance id and position so storing again replaces them
+        let mut n_event: u32 = 0;
+        for (actor_id, act) in self.0.iter() {
+            for (i, event) in act.get_events().iter().enumerate() {
+                let mut event_id = perf_id.to_vec();
+                event_id.extend_from_slice(&n_event.to_be_bytes());
+                n_event += 1;
+                let event_id = Bson::Binary(BinarySubtype::Generic, event_id);
+                let doc = doc! {
+                    "_id" => event_id.clone(),
+                    // The ac[t]or which emitted this event
+                    "t" => Bson::Binary(BinarySubtype::Generic, actor_id.to_vec()),
+                    // The [o]riginating performance id
+                    "o" => Bson::Binary(BinarySubtype::Generic, perf_id.to_vec()),
+                    // The [i]ndex of this event within the act
+                    "i" => i as i64,
+                    // The [c]hannel this event was emitted on, as provided by the script
+                    "c" => Bson::Binary(BinarySubtype::Generic, event.get_topic().to_vec()),
+                    // The [d]ata of this event, as provided by the script
+                    "d" => Bson::Binary(BinarySubtype::Generic, event.get_data().to_vec()),
+                };
+                db.upsert(&DataType::Event, doc! { "_id" : event_id }, doc)?;
+            }
+        }
+
+        let perf_key = Bson::Binary(BinarySubtype::Generic, perf_id.to_vec());
+        let doc = doc! {
+        "_id" => perf_key.clone(),
+        "v" => Bson::Binary(BinarySubtype::Generic, Bytes::from(self.clone()).to_vec())
+        };
+        db.upsert(&DataType::Performance, doc! { "_id" : perf_key }, doc)?;
+        Ok(())
+    }
+}
+
+// Fetch a page of events emitted by an actor, optionally filtered by topic
+pub fn fetch_events(
+    db: &MongoDB,
+    actor_id: Bytes,
+    topic: Option<Bytes>,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<Event>, Error> {
+    let mut doc = doc! {
+        "t" : Bson::Binary(BinarySubtype::Generic, actor_id.to_vec()),
+    };
+    if let Some(topic) = topic {
+        doc.insert("c", Bson::Binary(BinarySubtype::Generic, topic.to_vec()));
+    }
+    let events = db
+        .get_many(
+            &DataType::Event,
+            doc,
+            offset,
+            Some(limit.min(MAX_EVENTS_PER_FETCH)),
+        )?
+        .iter()
+        .map(|found_doc| {
+            Event::new(
+                Bytes::from(&found_doc.get_binary_generic("c").unwrap()[..]),
+                Bytes::from(&found_doc.get_binary_generic("d").unwrap()[..]),
+            )
+        })
+        .collect();
+    Ok(events)
+}
+
+// Remove the transaction and the state written by its performance
+pub fn revert_state(db: &MongoDB, perf_id: &Bytes) -> Result<(), Error> {
+    db.remove(
+        &DataType::State,
+        doc! { "o" : Bson::Binary(BinarySubtype::Generic, perf_id.to_vec()) },
+    )?;
+    db.remove(
+        &DataType::TX,
+        doc! { "_id" : Bson::Binary(BinarySubtype::Generic, perf_id.to_vec()) },
+    )?;
+    Ok(())
+}
+
+// Remove a performance and the events it emitted
+pub fn remove_performance(db: &MongoDB, perf_id: &Bytes) -> Result<(), Error> {
+    db.remove(
+        &DataType::Event,
+        doc! { "o" : Bson::Binary(BinarySubtype::Generic, perf_id.to_vec()) },
+    )?;
+    db.remove(
+        &DataType::Performance,
+        doc! { "_id" : Bson::Binary(BinarySubtype::Generic, perf_id.to_vec()) },
+    )?;
+    Ok(())
+}
+
+// Addresses are keyed by their versioned encoding
+fn address_key(addr: &SocketAddr) -> Bson {
+    Bson::Binary(
+        BinarySubtype::Generic,
+        Bytes::from(Peer::new(*addr)).to_vec(),
+    )
+}
+
+// Replace the persisted record for an address
+pub fn store_address(db: &MongoDB, record: &AddressRecord) -> Result<(), Error> {
+    let mut doc = doc! {
+        "_id" => address_key(&record.addr),
+        // Time [l]ast seen
+        "l" => record.last_seen as i64,
+        // Time of [a]ttempted last connection
+        "a" => record.last_attempt as i64,
+        // Number of [s]uccessful connections
+        "s" => record.n_successes as i64,
+        // Number of consecutive [f]ailed connections
+        "f" => record.n_failures as i64,
+    };
+    if let Some(pubkey) = record.pubkey {
+        // Last known public [k]ey
+        doc.insert(
+            "k",
+            Bson::Binary(BinarySubtype::Generic, bytes_from_pubkey(pubkey).to_vec()),
+        );
+    }
+    if let Some(banned_until) = record.banned_until {
+        // Time [b]anned until
+        doc.insert("b", banned_until as i64);
+    }
+    db.upsert(
+        &DataType::Address,
+        doc! { "_id" : address_key(&record.addr) },
+        doc,
+    )
+}
+
+pub fn remove_address(db: &MongoDB, addr: &SocketAddr) -> Result<(), Error> {
+    db.remove(&DataType::Address, doc! { "_id" : address_key(addr) })?;
+    Ok(())
+}
+
+// Malformed records are skipped
+pub fn load_addresses(db: &MongoDB) -> Result<Vec<AddressRecord>, Error> {
+    let records = db
+        .get_many(&DataType::Address, doc! {}, 0, None)?
+        .iter()
+        .filter_map(|found_doc| {
+            let raw = found_doc.get_binary_generic("_id").ok()?;
+            let addr = Peer::try_from(Bytes::from(&raw[..])).ok()?.get_addr();
+            let mut record = AddressRecord::new(addr, found_doc.get_i64("l").ok()? as u64);
+            record.last_attempt = found_doc.get_i64("a").ok()? as u64;
+            record.n_successes = found_doc.get_i64("s").ok()? as u32;
+            record.n_failures = found_doc.get_i64("f").ok()? as u32;
+            record.pubkey = found_doc
+                .get_binary_generic("k")
+                .ok()
+                .and_then(|raw| pubkey_from_bytes(Bytes::from(&raw[..])).ok());
+            record.banned_until = found_doc.get_i64("b").ok().map(|b| b as u64);
+            Some(record)
+        })
+        .collect();
+    Ok(records)
+}
+
 pub struct ValueStore(pub Bytes);
 
 impl Storable for ValueStore {
@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use bus::BusReader;
 use bytes::Bytes;
@@ -15,7 +16,7 @@ use crate::{
         signatures::ecdsa,
         sketches::{dummy_sketch::DummySketch, odd_sketch::OddSketch, SketchInsertable},
     },
-    net::messages::*,
+    net::{messages::*, transport::handshake_preimage},
     primitives::{
         status::{PeerStatus, Status},
         transaction::Transaction,
@@ -30,6 +31,7 @@ pub struct Ego {
     seckey: SecretKey,
 
     status: Status,
+    status_since: Instant, // Time of last status transition
 
     pub work_stack: WorkStack,
     pub minisketch: DummySketch,
@@ -42,6 +44,7 @@ impl Ego {
             pubkey,
             seckey,
             status: Default::default(),
+            status_since: Instant::now(),
             work_stack: Default::default(),
             current_distance: 512,
             minisketch: Default::default(),
@@ -55,13 +58,19 @@ impl Ego {
     pub fn update_status(&mut self, status: Status) {
         info!(target: "ego_event", "{} -> {}", self.status.to_str(), status.to_str());
         self.status = status;
+        self.status_since = Instant::now();
     }
 
-    pub fn generate_end_handshake(&self, secret: u64) -> Message {
+    pub fn get_status_elapsed(&self) -> Duration {
+        self.status_since.elapsed()
+    }
+
+    // Signing the session id prevents the signature being relayed to another session
+    pub fn generate_end_handshake(&self, secret: u64, session_id: &Bytes) -> Message {
         Message::EndHandshake {
             pubkey: self.pubkey,
             sig: ecdsa::sign(
-                &ecdsa::message_from_preimage(Bytes::from(VarInt::new(secret))),
+                &ecdsa::message_from_preimage(handshake_preimage(secret, session_id)),
                 &self.seckey,
             ),
         }
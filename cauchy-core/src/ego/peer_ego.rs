@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use futures::sync::mpsc::{channel, Receiver, Sender};
@@ -7,6 +8,7 @@ use log::info;
 use rand::Rng;
 use secp256k1::{PublicKey, SecretKey, Signature};
 use std::ops::Deref;
+use stream_cancel::{Trigger, Tripwire};
 
 use crate::{
     crypto::{
@@ -15,14 +17,14 @@ use crate::{
         sketches::{dummy_sketch::DummySketch, odd_sketch::OddSketch, SketchInsertable},
     },
     ego::ego::Ego,
-    net::messages::*,
+    net::{bans::Misbehaviour, messages::*, relay::InventorySet, transport::handshake_preimage},
     primitives::{
         status::{Expectation, PeerStatus},
         transaction::Transaction,
         varint::VarInt,
         work::{WorkSite, WorkStack, WorkState},
     },
-    utils::constants::{CONFIG, HASH_LEN},
+    utils::constants::{CONFIG, HASH_LEN, LOCAL_FEATURES, MAX_INVENTORY_PER_MSG, PROTOCOL_VERSION},
 };
 
 pub struct Perception {
@@ -49,22 +51,55 @@ pub struct PeerEgo {
     pubkey: Option<PublicKey>,
     sink: Sender<Message>,
     secret: u64,
+    session_id: Bytes,    // Identifies the encrypted session
+    version: Option<u64>, // Announced protocol version
+    features: u64,        // Announced feature flags
+    connected: Instant,
+    last_seen: Instant,                 // Time of the last message received
+    ping: Option<(u64, Instant)>,       // Outstanding ping nonce and when it was sent
+    rtt: Option<Duration>,              // Smoothed round trip time
+    peers_requested: bool,              // Awaiting a reply to our GetPeers
+    announced: InventorySet,            // Transactions the peer is known to have
+    sent: InventorySet,                 // Transactions relayed to the peer
+    requested: HashMap<Bytes, Instant>, // Transactions requested from the peer
+    looking_up: bool,                   // Announced transactions being looked up in the tx store
     status: PeerStatus,
+    status_since: Instant, // Time of last status transition
+    score: u32,            // Misbehaviour score
+    trigger: Option<Trigger>,
+    tripwire: Tripwire,
 
     // How peer perceives own ego
     pub perception: Option<Perception>,
 }
 
 impl PeerEgo {
-    pub fn new() -> (PeerEgo, Receiver<Message>) {
+    pub fn new(session_id: Bytes) -> (PeerEgo, Receiver<Message>) {
         let (peer_sink, peer_stream) = channel::<Message>(1024); // TODO: Unbounded? Handle errors
         let mut rng = rand::thread_rng();
+        let (trigger, tripwire) = Tripwire::new();
         (
             PeerEgo {
                 pubkey: None,
                 secret: rng.gen::<u64>(),
+                session_id,
+                version: None,
+                features: 0,
+                connected: Instant::now(),
+                last_seen: Instant::now(),
+                ping: None,
+                rtt: None,
+                peers_requested: false,
+                announced: InventorySet::with_capacity(CONFIG.relay.max_known),
+                sent: InventorySet::with_capacity(CONFIG.relay.max_known),
+                requested: HashMap::new(),
+                looking_up: false,
                 perception: None,
                 status: Default::default(),
+                status_since: Instant::now(),
+                score: 0,
+                trigger: Some(trigger),
+                tripwire,
                 sink: peer_sink,
             },
             peer_stream,
@@ -72,7 +107,8 @@ impl PeerEgo {
     }
 
     pub fn check_handshake(&mut self, sig: &Signature, pubkey: &PublicKey) {
-        let secret_msg = ecdsa::message_from_preimage(Bytes::from(VarInt::new(self.secret)));
+        let secret_msg =
+            ecdsa::message_from_preimage(handshake_preimage(self.secret, &self.session_id));
         if let Ok(true) = ecdsa::verify(&secret_msg, sig, pubkey) {
             self.pubkey = Some(*pubkey)
         }
@@ -86,6 +122,157 @@ impl PeerEgo {
         self.secret
     }
 
+    pub fn get_session_id(&self) -> Bytes {
+        self.session_id.clone()
+    }
+
+    pub fn set_version(&mut self, version: u64, features: u64) {
+        self.version = Some(version);
+        self.features = features;
+    }
+
+    // Highest version both sides speak
+    pub fn get_version(&self) -> Option<u64> {
+        self.version.map(|version| version.min(PROTOCOL_VERSION))
+    }
+
+    // Whether both sides understand the feature's messages
+    pub fn supports(&self, feature: u64) -> bool {
+        self.features & LOCAL_FEATURES & feature == feature
+    }
+
+    // Only one ping is outstanding at a time
+    pub fn ping(&mut self) {
+        if self.ping.is_some() {
+            return;
+        }
+        let nonce = rand::thread_rng().gen::<u64>();
+        self.ping = Some((nonce, Instant::now()));
+        self.send_msg(Message::Ping { nonce });
+    }
+
+    // Returns false if the pong answers no outstanding ping
+    pub fn pong(&mut self, nonce: u64) -> bool {
+        match self.ping {
+            Some((expected, sent)) if expected == nonce => {
+                let sample = sent.elapsed();
+                self.rtt = Some(match self.rtt {
+                    Some(rtt) => (rtt * 7 + sample) / 8,
+                    None => sample,
+                });
+                self.ping = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get_rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    // Peers are only accepted in reply to our own request
+    pub fn request_peers(&mut self) {
+        self.peers_requested = true;
+    }
+
+    // Returns false if no request is outstanding
+    pub fn take_peers_request(&mut self) -> bool {
+        std::mem::replace(&mut self.peers_requested, false)
+    }
+
+    // Missed a keepalive, or never announced a version
+    pub fn is_unresponsive(&self, timeout: Duration) -> bool {
+        match self.ping {
+            Some((_, sent)) => sent.elapsed() > timeout,
+            None => self.version.is_none() && self.connected.elapsed() > timeout,
+        }
+    }
+
+    pub fn mark_seen(&mut self) {
+        self.last_seen = Instant::now();
+    }
+
+    // No message received within the timeout
+    pub fn is_silent(&self, timeout: Duration) -> bool {
+        self.last_seen.elapsed() > timeout
+    }
+
+    // Announce transactions the peer is not known to have
+    pub fn announce(&mut self, ids: &[Bytes]) {
+        let new_ids: Vec<Bytes> = ids
+            .iter()
+            .filter(|id| self.announced.insert((*id).clone()))
+            .cloned()
+            .collect();
+        for chunk in new_ids.chunks(MAX_INVENTORY_PER_MSG) {
+            self.send_msg(Message::Inventory {
+                ids: chunk.iter().cloned().collect(),
+            });
+        }
+    }
+
+    // Transactions not previously announced by the peer, recorded as known to it
+    pub fn newly_announced(&mut self, ids: HashSet<Bytes>) -> Vec<Bytes> {
+        ids.into_iter()
+            .filter(|id| self.announced.insert(id.clone()))
+            .collect()
+    }
+
+    pub fn mark_announced<I>(&mut self, ids: I)
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        for id in ids {
+            self.announced.insert(id);
+        }
+    }
+
+    pub fn was_sent(&self, id: &Bytes) -> bool {
+        self.sent.contains(id)
+    }
+
+    pub fn mark_sent(&mut self, id: Bytes) {
+        self.sent.insert(id);
+    }
+
+    // Number of further transactions that may be requested
+    pub fn request_room(&self) -> usize {
+        MAX_INVENTORY_PER_MSG.saturating_sub(self.requested.len())
+    }
+
+    pub fn add_requests(&mut self, ids: &HashSet<Bytes>) {
+        let now = Instant::now();
+        self.requested
+            .extend(ids.iter().map(|id| (id.clone(), now)));
+    }
+
+    // Forget requests the peer never served, such as transactions it has since reverted
+    pub fn expire_requests(&mut self, timeout: Duration) {
+        self.requested
+            .retain(|_, requested| requested.elapsed() < timeout);
+    }
+
+    // Whether the transactions answer outstanding requests, which are then cleared
+    pub fn fulfil_requests(&mut self, ids: &[Bytes]) -> bool {
+        if ids.is_empty() || !ids.iter().all(|id| self.requested.contains_key(id)) {
+            return false;
+        }
+        for id in ids {
+            self.requested.remove(id);
+        }
+        true
+    }
+
+    // Returns false if a lookup is already in flight
+    pub fn start_lookup(&mut self) -> bool {
+        !std::mem::replace(&mut self.looking_up, true)
+    }
+
+    pub fn end_lookup(&mut self) {
+        self.looking_up = false;
+    }
+
     pub fn get_status(&self) -> PeerStatus {
         self.status.clone()
     }
@@ -94,6 +281,10 @@ impl PeerEgo {
         &mut self.status
     }
 
+    pub fn get_status_elapsed(&self) -> Duration {
+        self.status_since.elapsed()
+    }
+
     pub fn get_pubkey(&self) -> Option<PublicKey> {
         self.pubkey
     }
@@ -112,9 +303,49 @@ impl PeerEgo {
         }
     }
 
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    // Disconnect once the score reaches the ban threshold
+    pub fn penalise(&mut self, misbehaviour: Misbehaviour) {
+        self.score += misbehaviour.penalty();
+        info!(
+            target: "ego_event",
+            "penalised for {}, score {}",
+            misbehaviour.to_str(),
+            self.score
+        );
+        if self.is_banned() {
+            self.disconnect();
+        }
+    }
+
+    pub fn is_banned(&self) -> bool {
+        self.score >= CONFIG.banning.threshold
+    }
+
+    // Carry over misbehaviour from earlier connections
+    pub fn raise_score(&mut self, score: u32) {
+        self.score = self.score.max(score);
+        if self.is_banned() {
+            self.disconnect();
+        }
+    }
+
+    // Resolves once the peer should be disconnected
+    pub fn get_tripwire(&self) -> Tripwire {
+        self.tripwire.clone()
+    }
+
+    pub fn disconnect(&mut self) {
+        self.trigger.take();
+    }
+
     pub fn update_status(&mut self, status: PeerStatus) {
         info!("{} -> {}", self.status.to_str(), status.to_str());
         self.status = status;
+        self.status_since = Instant::now();
     }
 
     pub fn send_msg(&self, message: Message) {
@@ -123,13 +354,13 @@ impl PeerEgo {
                 .clone()
                 .send(message)
                 .and_then(|_| futures::future::ok(()))
-                .map_err(|_| panic!()),
+                .map_err(|_| ()), // Peer disconnected
         );
     }
 
     // Update reported
     pub fn pull_work(&mut self, work_stack: WorkStack) {
-        self.status = PeerStatus::Fighting(work_stack);
+        self.update_status(PeerStatus::Fighting(work_stack));
     }
 
     // Update pending
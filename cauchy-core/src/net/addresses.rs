@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+
+use log::info;
+use rand::seq::SliceRandom;
+use secp256k1::PublicKey;
+
+use super::peers::{Peer, Peers};
+use crate::utils::timing::get_current_time;
+
+// Persisted knowledge of a peer address, times are in milliseconds since the epoch
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressRecord {
+    pub addr: SocketAddr,
+    pub last_seen: u64,
+    pub pubkey: Option<PublicKey>,
+    pub banned_until: Option<u64>,
+    pub last_attempt: u64,
+    pub n_successes: u32,
+    pub n_failures: u32,
+}
+
+impl AddressRecord {
+    pub fn new(addr: SocketAddr, last_seen: u64) -> AddressRecord {
+        AddressRecord {
+            addr,
+            last_seen,
+            pubkey: None,
+            banned_until: None,
+            last_attempt: 0,
+            n_successes: 0,
+            n_failures: 0,
+        }
+    }
+
+    pub fn is_banned(&self, now: u64) -> bool {
+        match self.banned_until {
+            Some(banned_until) => banned_until > now,
+            None => false,
+        }
+    }
+
+    // Exponential backoff after consecutive failures
+    pub fn next_attempt(&self, backoff_base: u64, backoff_max: u64) -> u64 {
+        if self.n_failures == 0 {
+            return self.last_attempt;
+        }
+        let backoff = 2u64
+            .checked_pow(self.n_failures - 1)
+            .and_then(|factor| backoff_base.checked_mul(factor))
+            .unwrap_or(backoff_max)
+            .min(backoff_max);
+        self.last_attempt + backoff
+    }
+}
+
+// IPv4 peers accepted on a dual stack socket appear as IPv4-mapped IPv6 addresses
+pub fn normalise(addr: SocketAddr) -> SocketAddr {
+    if let IpAddr::V6(ip) = addr.ip() {
+        if ip.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] {
+            if let Some(ip) = ip.to_ipv4() {
+                return SocketAddr::new(IpAddr::V4(ip), addr.port());
+            }
+        }
+    }
+    addr
+}
+
+// Outbound connections are spread across address groups, local addresses are not grouped
+pub fn address_group(addr: &SocketAddr) -> Option<Vec<u8>> {
+    match normalise(*addr).ip() {
+        IpAddr::V4(ip) => {
+            if ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() {
+                return None;
+            }
+            let octets = ip.octets();
+            Some(vec![4, octets[0], octets[1]])
+        }
+        IpAddr::V6(ip) => {
+            let prefix = ip.segments()[0];
+            let unique_local = prefix & 0xfe00 == 0xfc00; // fc00::/7
+            let link_local = prefix & 0xffc0 == 0xfe80; // fe80::/10
+            if ip.is_loopback() || ip.is_unspecified() || unique_local || link_local {
+                return None;
+            }
+            let mut group = vec![6];
+            group.extend_from_slice(&ip.octets()[..4]);
+            Some(group)
+        }
+    }
+}
+
+// Gossiped addresses must be dialable, loopback only when gossiped over loopback
+pub fn is_valid_gossip(addr: &SocketAddr, source: &SocketAddr) -> bool {
+    let ip = addr.ip();
+    if addr.port() == 0 || ip.is_unspecified() || ip.is_multicast() {
+        return false;
+    }
+    if let IpAddr::V4(ip) = ip {
+        if ip.is_broadcast() || ip.is_documentation() {
+            return false;
+        }
+    }
+    !ip.is_loopback() || source.ip().is_loopback()
+}
+
+// Dialable peer addresses learned from the network
+pub struct AddressManager {
+    records: HashMap<SocketAddr, AddressRecord>,
+    dirty: HashSet<SocketAddr>, // Changed since last taken
+    capacity: usize,
+}
+
+impl AddressManager {
+    pub fn with_capacity(capacity: usize) -> AddressManager {
+        AddressManager {
+            records: HashMap::with_capacity(capacity),
+            dirty: HashSet::new(),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn contains(&self, addr: &SocketAddr) -> bool {
+        self.records.contains_key(addr)
+    }
+
+    pub fn get(&self, addr: &SocketAddr) -> Option<&AddressRecord> {
+        self.records.get(addr)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &AddressRecord> {
+        self.records.values()
+    }
+
+    // Evicts the least recently seen address, preferring those never connected to, when full
+    fn make_room(&mut self, addr: &SocketAddr) {
+        if self.records.contains_key(addr) || self.records.len() < self.capacity {
+            return;
+        }
+        let oldest = self
+            .records
+            .values()
+            .min_by_key(|record| (record.n_successes > 0, record.last_seen))
+            .map(|record| record.addr);
+        if let Some(oldest) = oldest {
+            self.remove(&oldest);
+        }
+    }
+
+    fn entry(&mut self, addr: SocketAddr) -> Option<&mut AddressRecord> {
+        if self.capacity == 0 {
+            return None;
+        }
+        self.make_room(&addr);
+        self.dirty.insert(addr);
+        let now = get_current_time();
+        Some(
+            self.records
+                .entry(addr)
+                .or_insert_with(|| AddressRecord::new(addr, now)),
+        )
+    }
+
+    pub fn insert(&mut self, addr: SocketAddr) {
+        if let Some(record) = self.entry(addr) {
+            record.last_seen = get_current_time();
+        }
+    }
+
+    // Gossip is not evidence the address is live, so known records are left untouched
+    pub fn insert_batch(&mut self, peers: Peers, source: &SocketAddr) {
+        let mut n_new = 0;
+        for peer in peers.into_vec() {
+            let addr = normalise(peer.get_addr());
+            if !is_valid_gossip(&addr, source) || self.contains(&addr) {
+                continue;
+            }
+            if self.entry(addr).is_some() {
+                n_new += 1;
+            }
+        }
+        info!(target: "arena_event", "learned {} new addresses", n_new);
+    }
+
+    // Loaded records are already persisted
+    pub fn insert_record(&mut self, record: AddressRecord) {
+        self.make_room(&record.addr);
+        self.dirty.remove(&record.addr);
+        self.records.insert(record.addr, record);
+    }
+
+    pub fn record_success(&mut self, addr: SocketAddr, pubkey: PublicKey) {
+        if let Some(record) = self.entry(addr) {
+            record.last_seen = get_current_time();
+            record.pubkey = Some(pubkey);
+            record.n_successes += 1;
+            record.n_failures = 0;
+        }
+    }
+
+    // Forgets the address once it has failed too many times in a row
+    pub fn record_failure(&mut self, addr: SocketAddr, max_failures: u32) {
+        let n_failures = match self.records.get_mut(&addr) {
+            Some(record) => {
+                record.n_failures += 1;
+                record.n_failures
+            }
+            None => return,
+        };
+        self.dirty.insert(addr);
+        if n_failures >= max_failures {
+            self.remove(&addr);
+        }
+    }
+
+    pub fn record_attempt(&mut self, addr: SocketAddr, now: u64) {
+        if let Some(record) = self.records.get_mut(&addr) {
+            record.last_attempt = now;
+            self.dirty.insert(addr);
+        }
+    }
+
+    // Only known addresses are marked, a banned inbound peer's ephemeral address is not added
+    pub fn record_ban(&mut self, addr: SocketAddr, pubkey: Option<PublicKey>, until: u64) {
+        if let Some(record) = self.records.get_mut(&addr) {
+            self.dirty.insert(addr);
+            record.banned_until = Some(until);
+            if pubkey.is_some() {
+                record.pubkey = pubkey;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        if self.records.remove(addr).is_some() {
+            self.dirty.insert(*addr);
+        }
+    }
+
+    // Records changed since the last call, and addresses removed since
+    pub fn take_dirty(&mut self) -> (Vec<AddressRecord>, Vec<SocketAddr>) {
+        let mut updated = vec![];
+        let mut removed = vec![];
+        for addr in self.dirty.drain() {
+            match self.records.get(&addr) {
+                Some(record) => updated.push(record.clone()),
+                None => removed.push(addr),
+            }
+        }
+        (updated, removed)
+    }
+
+    // Random selection of at most n unbanned addresses
+    pub fn sample_n(&self, n: usize) -> Peers {
+        let now = get_current_time();
+        let addrs: Vec<SocketAddr> = self
+            .records
+            .values()
+            .filter(|record| !record.is_banned(now))
+            .map(|record| record.addr)
+            .collect();
+        let mut rng = &mut rand::thread_rng();
+        Peers::new(
+            addrs
+                .choose_multiple(&mut rng, n)
+                .map(|addr| Peer::new(*addr))
+                .collect(),
+        )
+    }
+
+    // At most n addresses to dial, previously good peers first, skipping those excluded,
+    // backing off and those in an address group already used
+    pub fn dial_candidates(
+        &self,
+        n: usize,
+        exclude: &HashSet<SocketAddr>,
+        groups: &HashSet<Vec<u8>>,
+        backoff: (u64, u64),
+    ) -> Vec<SocketAddr> {
+        let now = get_current_time();
+        let (backoff_base, backoff_max) = backoff;
+        let mut candidates: Vec<&AddressRecord> = self
+            .records
+            .values()
+            .filter(|record| {
+                !exclude.contains(&record.addr)
+                    && !record.is_banned(now)
+                    && record.next_attempt(backoff_base, backoff_max) <= now
+            })
+            .collect();
+        let mut rng = &mut rand::thread_rng();
+        candidates.shuffle(&mut rng);
+        candidates.sort_by_key(|record| record.n_successes == 0);
+
+        let mut groups = groups.clone();
+        let mut selected = Vec::with_capacity(n);
+        for record in candidates {
+            if selected.len() == n {
+                break;
+            }
+            if let Some(group) = address_group(&record.addr) {
+                if !groups.insert(group) {
+                    continue;
+                }
+            }
+            selected.push(record.addr);
+        }
+        selected
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use log::info;
+use secp256k1::PublicKey;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Misbehaviour {
+    UnsolicitedWork,
+    UnsolicitedMiniSketch,
+    FraudulentMiniSketch,
+    InvalidTransactions,
+    TransactionsWhilePushing,
+    UnexpectedNegAck,
+    UnsolicitedPong,
+    UnsolicitedPeers,
+    WorkTimeout,
+    ReconcileTimeout,
+    InvalidReconcilePayload,
+    MalformedMessage,
+    InvalidHandshake,
+}
+
+impl Misbehaviour {
+    pub fn penalty(self) -> u32 {
+        match self {
+            Misbehaviour::UnsolicitedWork => 10,
+            Misbehaviour::UnsolicitedMiniSketch => 10,
+            Misbehaviour::UnexpectedNegAck => 10,
+            Misbehaviour::UnsolicitedPong => 10,
+            Misbehaviour::UnsolicitedPeers => 10,
+            Misbehaviour::WorkTimeout => 10,
+            Misbehaviour::ReconcileTimeout => 10,
+            Misbehaviour::TransactionsWhilePushing => 20,
+            Misbehaviour::InvalidTransactions => 50,
+            Misbehaviour::FraudulentMiniSketch => 100,
+            Misbehaviour::InvalidReconcilePayload => 100,
+            Misbehaviour::MalformedMessage => 25,
+            Misbehaviour::InvalidHandshake => 50,
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Misbehaviour::UnsolicitedWork => "unsolicited work",
+            Misbehaviour::UnsolicitedMiniSketch => "unsolicited minisketch",
+            Misbehaviour::UnexpectedNegAck => "unexpected negack",
+            Misbehaviour::UnsolicitedPong => "unsolicited pong",
+            Misbehaviour::UnsolicitedPeers => "unsolicited peers",
+            Misbehaviour::WorkTimeout => "work timeout",
+            Misbehaviour::ReconcileTimeout => "reconcile timeout",
+            Misbehaviour::TransactionsWhilePushing => "transactions while pushing",
+            Misbehaviour::InvalidTransactions => "invalid transactions",
+            Misbehaviour::FraudulentMiniSketch => "fraudulent minisketch",
+            Misbehaviour::InvalidReconcilePayload => "invalid reconcile payload",
+            Misbehaviour::MalformedMessage => "malformed message",
+            Misbehaviour::InvalidHandshake => "invalid handshake",
+        }
+    }
+}
+
+// Timed bans keyed by address and public key
+#[derive(Default)]
+pub struct BanList {
+    addrs: HashMap<IpAddr, Instant>,
+    pubkeys: HashMap<PublicKey, Instant>,
+}
+
+impl BanList {
+    pub fn ban(&mut self, addr: &SocketAddr, pubkey: Option<PublicKey>, duration: Duration) {
+        info!(target: "arena_event", "banning {}", addr);
+        let until = Instant::now() + duration;
+        self.addrs.insert(addr.ip(), until);
+        if let Some(pubkey) = pubkey {
+            self.pubkeys.insert(pubkey, until);
+        }
+    }
+
+    pub fn is_banned(&mut self, addr: &SocketAddr) -> bool {
+        let now = Instant::now();
+        self.addrs.retain(|_, until| *until > now);
+        self.addrs.contains_key(&addr.ip())
+    }
+
+    pub fn is_banned_pubkey(&mut self, pubkey: &PublicKey) -> bool {
+        let now = Instant::now();
+        self.pubkeys.retain(|_, until| *until > now);
+        self.pubkeys.contains_key(pubkey)
+    }
+}
+
+// Score left once a point has been shed every decay period
+fn decayed((score, since): (u32, Instant), decay: Duration) -> u32 {
+    let points = since.elapsed().as_millis() / decay.as_millis().max(1);
+    score.saturating_sub(points.min(u128::from(u32::MAX)) as u32)
+}
+
+// Misbehaviour scores outliving connections, keyed by address and public key
+pub struct ScoreBook {
+    addrs: HashMap<IpAddr, (u32, Instant)>,
+    pubkeys: HashMap<PublicKey, (u32, Instant)>,
+    decay: Duration, // Time for a score to drop by one point
+}
+
+impl ScoreBook {
+    pub fn new(decay: Duration) -> ScoreBook {
+        ScoreBook {
+            addrs: HashMap::new(),
+            pubkeys: HashMap::new(),
+            decay,
+        }
+    }
+
+    // Keep the higher of the recorded and current scores, forgetting those fully decayed
+    pub fn record(&mut self, addr: &SocketAddr, pubkey: Option<PublicKey>, score: u32) {
+        let now = Instant::now();
+        let score = score.max(self.score(addr, pubkey.as_ref()));
+        self.addrs.insert(addr.ip(), (score, now));
+        if let Some(pubkey) = pubkey {
+            self.pubkeys.insert(pubkey, (score, now));
+        }
+
+        let decay = self.decay;
+        self.addrs.retain(|_, entry| decayed(*entry, decay) > 0);
+        self.pubkeys.retain(|_, entry| decayed(*entry, decay) > 0);
+    }
+
+    // Highest decayed score of the address and public key
+    pub fn score(&self, addr: &SocketAddr, pubkey: Option<&PublicKey>) -> u32 {
+        let addr_entry = self.addrs.get(&addr.ip());
+        let pubkey_entry = pubkey.and_then(|pubkey| self.pubkeys.get(pubkey));
+        addr_entry
+            .into_iter()
+            .chain(pubkey_entry)
+            .map(|entry| decayed(*entry, self.decay))
+            .max()
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,97 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::sync::mpsc::Sender;
+use log::{error, info};
+use tokio::net::TcpStream;
+use tokio::prelude::*;
+use tokio::timer::{Interval, Timeout};
+
+use crate::{
+    db::{blocking, mongodb::MongoDB, storing::*},
+    net::addresses::AddressRecord,
+    primitives::arena::*,
+    utils::constants::*,
+};
+
+// Write address book changes through to the database
+fn persist_addresses(db: &MongoDB, updated: Vec<AddressRecord>, removed: Vec<SocketAddr>) {
+    for record in updated {
+        if let Err(err) = store_address(db, &record) {
+            error!(target: "arena_event", "failed to store address {}: {}", record.addr, err);
+        }
+    }
+    for addr in removed {
+        if let Err(err) = remove_address(db, &addr) {
+            error!(target: "arena_event", "failed to remove address {}: {}", addr, err);
+        }
+    }
+}
+
+// Dial known addresses until outbound connections are at target
+fn dial_peers(arena: &Arc<Mutex<Arena>>, socket_send: &Sender<TcpStream>) {
+    let mut arena_guard = arena.lock().unwrap();
+    let target = CONFIG.connections.target_outbound;
+    let n_outbound = arena_guard.n_outbound();
+    info!(
+        target: "arena_event",
+        "{} outbound and {} inbound peers",
+        n_outbound,
+        arena_guard.n_inbound()
+    );
+    if n_outbound >= target {
+        return;
+    }
+
+    // Ask for more addresses when running low
+    if arena_guard.n_addresses() < target {
+        arena_guard.peers_pulse();
+    }
+
+    let candidates = arena_guard.dial_candidates(target - n_outbound);
+    drop(arena_guard);
+
+    for addr in candidates {
+        info!(target: "arena_event", "dialing {}", addr);
+        let socket_send_inner = socket_send.clone();
+        let arena_inner = arena.clone();
+        let arena_send = arena.clone();
+        let dial = Timeout::new(
+            TcpStream::connect(&addr),
+            CONFIG.connections.connect_timeout_ms,
+        );
+        tokio::spawn(
+            dial.map_err(move |err| {
+                error!(target: "arena_event", "failed to dial {}: {}", addr, err);
+                arena_inner.lock().unwrap().dial_failed(addr);
+            })
+            .and_then(move |socket| {
+                socket_send_inner.send(socket).map_err(move |_| {
+                    error!(target: "arena_event", "failed to hand over {}", addr);
+                    arena_send.lock().unwrap().dial_aborted(&addr);
+                })
+            })
+            .map(|_| ()),
+        );
+    }
+}
+
+// Keeps outbound connections at target, replacing those dropped
+pub fn manager(
+    arena: Arc<Mutex<Arena>>,
+    socket_send: Sender<TcpStream>,
+    db: MongoDB,
+) -> impl Future<Item = (), Error = ()> {
+    // Dial immediately to reconnect to known peers
+    Interval::new(Instant::now(), CONFIG.connections.dial_interval_ms)
+        .map_err(|_| ()) // TODO: Catch?
+        .for_each(move |_| {
+            dial_peers(&arena, &socket_send);
+
+            // Changes are snapshotted under the lock and written before the next tick
+            let (updated, removed) = arena.lock().unwrap().take_dirty_addresses();
+            let db = db.clone();
+            blocking(move || persist_addresses(&db, updated, removed))
+        })
+}
@@ -16,7 +16,8 @@ pub fn heartbeat(arena: Arc<Mutex<Arena>>) -> impl Future<Item = (), Error = ()>
     Interval::new_interval(CONFIG.network.heartbeat_ms)
         .map_err(|_| ()) // TODO: Catch?
         .for_each(move |_| {
-            let arena_guard = arena.lock().unwrap();
+            let mut arena_guard = arena.lock().unwrap();
+            arena_guard.timeout_sweep(&CONFIG.timeouts);
 
             // Only heartbeat when idle
             if arena_guard.get_ego().lock().unwrap().get_status() == Status::Idle {
@@ -35,3 +36,12 @@ pub fn heartbeat(arena: Arc<Mutex<Arena>>) -> impl Future<Item = (), Error = ()>
             }
         })
 }
+
+pub fn keepalive(arena: Arc<Mutex<Arena>>) -> impl Future<Item = (), Error = ()> {
+    Interval::new_interval(CONFIG.keepalive.ping_interval_ms)
+        .map_err(|_| ()) // TODO: Catch?
+        .for_each(move |_| {
+            arena.lock().unwrap().keepalive_pulse();
+            Ok(())
+        })
+}
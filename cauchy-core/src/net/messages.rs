@@ -1,4 +1,6 @@
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use bytes::Bytes;
 use bytes::{Buf, BufMut, BytesMut, IntoBuf};
@@ -8,7 +10,7 @@ use secp256k1::key::PublicKey;
 use secp256k1::Signature;
 use tokio::codec::{Decoder, Encoder};
 
-use super::peers::{Peer, Peers};
+use super::peers::{Peers, MAX_ADDR_LEN};
 use crate::{
     crypto::{
         signatures::ecdsa::*,
@@ -25,17 +27,79 @@ use crate::{
 pub enum Message {
     StartHandshake { secret: u64 }, // 0 || Secret VarInt
     EndHandshake { pubkey: PublicKey, sig: Signature }, // 1 || Pk || Sig
-    Work(WorkStack),                // 2 || OddSketch || Root || Nonce
+    Work(WorkStack),                // 2 || OddSketch Length VarInt || OddSketch || Root || Nonce
     MiniSketch { minisketch: DummySketch }, // 3 || Number of Rows VarInt || IBLT
     GetTransactions { ids: HashSet<Bytes> }, // 4 || Number of Ids VarInt || Ids
     Transactions { txs: Vec<Transaction> }, // 5 || Number of Bytes VarInt || Tx ...
     Reconcile,                      // 6
     ReconcileNegAck,                // 7
     GetWork,                        // 8
-    Peers { peers: Peers },         // 9 || Number of peers || Peers
+    Peers { peers: Peers },         // 9 || Number of peers || Versioned addresses
+    GetPeers,                       // 10
+    Version { version: u64, features: u64 }, // 11 || Version VarInt || Features VarInt
+    Ping { nonce: u64 },            // 12 || Nonce VarInt
+    Pong { nonce: u64 },            // 13 || Nonce VarInt
+    Inventory { ids: HashSet<Bytes> }, // 14 || Number of Ids VarInt || Ids
 }
 
-pub struct MessageCodec;
+// Tags beyond are from newer protocol versions
+pub const MAX_MESSAGE_TAG: u8 = 14;
+
+const MAX_VARINT_LEN: usize = 10;
+
+// Largest encoding of each message type, frames beyond are rejected
+pub fn max_message_len(tag: u8) -> usize {
+    match tag {
+        0 | 12 | 13 => 1 + MAX_VARINT_LEN,
+        1 => 1 + PUBKEY_LEN + SIG_LEN,
+        2 => 1 + MAX_VARINT_LEN + SKETCH_CAPACITY + HASH_LEN + MAX_VARINT_LEN,
+        3 | 4 => 1 + MAX_VARINT_LEN + MAX_IDS_PER_MSG * HASH_LEN,
+        14 => 1 + MAX_VARINT_LEN + MAX_INVENTORY_PER_MSG * HASH_LEN,
+        6 | 7 | 8 | 10 => 1,
+        5 => 1 + MAX_VARINT_LEN + MAX_TXS_PAYLOAD_LEN,
+        9 => 1 + MAX_VARINT_LEN + MAX_PEERS_PER_MSG * MAX_ADDR_LEN,
+        11 => 1 + 2 * MAX_VARINT_LEN,
+        _ => MAX_FRAME_LEN,
+    }
+}
+
+// Protocol version agreed with a peer, shared by its codec and connection
+#[derive(Clone)]
+pub struct NegotiatedVersion(Arc<AtomicU64>);
+
+impl Default for NegotiatedVersion {
+    fn default() -> Self {
+        NegotiatedVersion(Arc::new(AtomicU64::new(PROTOCOL_VERSION)))
+    }
+}
+
+impl NegotiatedVersion {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, version: u64) {
+        self.0.store(version, Ordering::Relaxed)
+    }
+}
+
+// Messages are encoded for the negotiated version, the latest until the peer announces one
+#[derive(Clone, Default)]
+pub struct MessageCodec {
+    version: NegotiatedVersion,
+}
+
+impl MessageCodec {
+    pub fn with_version(version: u64) -> MessageCodec {
+        let codec = MessageCodec::default();
+        codec.version.set(version);
+        codec
+    }
+
+    pub fn get_version(&self) -> NegotiatedVersion {
+        self.version.clone()
+    }
+}
 
 impl Encoder for MessageCodec {
     type Item = Message;
@@ -59,9 +123,11 @@ impl Encoder for MessageCodec {
             Message::Work(work_stack) => {
                 info!(target: "encoding_event", "encoding work");
                 dst.put_u8(2);
-                // TODO: Variable length
-                //dst.extend(Bytes::from(VarInt::new(sketch.len() as u64)));
-                dst.extend(Bytes::from(work_stack.get_oddsketch()));
+                let sketch = Bytes::from(work_stack.get_oddsketch());
+                if self.version.get() >= WORK_SKETCH_LEN_VERSION {
+                    dst.extend(Bytes::from(VarInt::new(sketch.len() as u64)));
+                }
+                dst.extend(sketch);
                 dst.extend(work_stack.get_root());
                 dst.extend(Bytes::from(VarInt::new(work_stack.get_nonce())));
             }
@@ -73,10 +139,7 @@ impl Encoder for MessageCodec {
             Message::GetTransactions { ids } => {
                 info!(target: "encoding_event", "encoding tx request");
                 dst.put_u8(4);
-                dst.extend(Bytes::from(VarInt::new(ids.len() as u64)));
-                for id in ids {
-                    dst.extend(id);
-                }
+                encode_ids(ids, dst);
             }
             Message::Transactions { txs } => {
                 info!(target: "encoding_event", "encoding txs");
@@ -95,14 +158,70 @@ impl Encoder for MessageCodec {
             Message::ReconcileNegAck => dst.put_u8(7),
             Message::GetWork => dst.put_u8(8),
             Message::Peers { peers } => {
+                info!(target: "encoding_event", "encoding peers");
                 dst.put_u8(9);
                 dst.extend(Bytes::from(peers));
             }
+            Message::GetPeers => dst.put_u8(10),
+            Message::Version { version, features } => {
+                info!(target: "encoding_event", "encoding version");
+                dst.put_u8(11);
+                dst.extend(Bytes::from(VarInt::new(version)));
+                dst.extend(Bytes::from(VarInt::new(features)));
+            }
+            Message::Ping { nonce } => {
+                dst.put_u8(12);
+                dst.extend(Bytes::from(VarInt::new(nonce)));
+            }
+            Message::Pong { nonce } => {
+                dst.put_u8(13);
+                dst.extend(Bytes::from(VarInt::new(nonce)));
+            }
+            Message::Inventory { ids } => {
+                info!(target: "encoding_event", "encoding inventory");
+                dst.put_u8(14);
+                encode_ids(ids, dst);
+            }
         }
         Ok(())
     }
 }
 
+fn encode_ids(ids: HashSet<Bytes>, dst: &mut BytesMut) {
+    dst.extend(Bytes::from(VarInt::new(ids.len() as u64)));
+    for id in ids {
+        dst.extend(id);
+    }
+}
+
+// Parse a VarInt count followed by that many ids, at most max_ids
+fn parse_ids<T: Buf>(
+    buf: &mut T,
+    max_ids: usize,
+) -> Result<Option<(HashSet<Bytes>, usize)>, Error> {
+    let (n_ids_vi, n_ids_vi_len) = match VarInt::parse_buf(buf)? {
+        Some(some) => some,
+        None => return Ok(None),
+    };
+    let us_n_ids = usize::from(n_ids_vi);
+    info!(target: "decoding_event", "number of ids to decode {}", us_n_ids);
+    if us_n_ids > max_ids {
+        return Err(MalformedMessageError.into());
+    }
+    let total_size = us_n_ids * HASH_LEN;
+    if buf.remaining() < total_size {
+        return Ok(None);
+    }
+
+    let mut ids = HashSet::with_capacity(us_n_ids);
+    for _ in 0..us_n_ids {
+        let mut id_dst = [0; HASH_LEN];
+        buf.copy_to_slice(&mut id_dst);
+        ids.insert(Bytes::from(&id_dst[..]));
+    }
+    Ok(Some((ids, n_ids_vi_len + total_size)))
+}
+
 impl Decoder for MessageCodec {
     type Item = Message;
     type Error = Error;
@@ -145,6 +264,18 @@ impl Decoder for MessageCodec {
             }
             2 => {
                 info!(target: "decoding_event", "decoding work");
+                // Earlier versions omit the sketch length
+                let mut sketch_len_len = 0;
+                if self.version.get() >= WORK_SKETCH_LEN_VERSION {
+                    let (sketch_len_vi, len) = match VarInt::parse_buf(&mut buf)? {
+                        Some(some) => some,
+                        None => return Ok(None),
+                    };
+                    if usize::from(sketch_len_vi) != SKETCH_CAPACITY {
+                        return Err(MalformedMessageError.into());
+                    }
+                    sketch_len_len = len;
+                }
                 if buf.remaining() < SKETCH_CAPACITY + HASH_LEN {
                     return Ok(None);
                 }
@@ -164,7 +295,7 @@ impl Decoder for MessageCodec {
                     OddSketch::from(&sketch_dst[..]),
                     u64::from(nonce_vi),
                 ));
-                src.advance(1 + SKETCH_CAPACITY + HASH_LEN + len);
+                src.advance(1 + sketch_len_len + SKETCH_CAPACITY + HASH_LEN + len);
                 Ok(Some(msg))
             }
             3 => {
@@ -179,27 +310,12 @@ impl Decoder for MessageCodec {
             }
             4 => {
                 info!(target: "decoding_event", "decoding get transactions");
-                let (n_tx_ids_vi, n_tx_ids_vi_len) = match VarInt::parse_buf(&mut buf)? {
+                let (ids, len) = match parse_ids(&mut buf, MAX_IDS_PER_MSG)? {
                     Some(some) => some,
                     None => return Ok(None),
                 };
-                let us_n_tx_ids = usize::from(n_tx_ids_vi);
-                info!(target: "decoding_event", "number of txns to decode {}", us_n_tx_ids);
-                let total_size = us_n_tx_ids * HASH_LEN;
-                let mut ids = HashSet::with_capacity(us_n_tx_ids);
-
-                if buf.remaining() < total_size {
-                    Ok(None)
-                } else {
-                    for _ in 0..us_n_tx_ids {
-                        let mut id_dst = [0; HASH_LEN];
-                        buf.copy_to_slice(&mut id_dst);
-                        ids.insert(Bytes::from(&id_dst[..]));
-                    }
-                    let msg = Message::GetTransactions { ids };
-                    src.advance(1 + n_tx_ids_vi_len + total_size);
-                    Ok(Some(msg))
-                }
+                src.advance(1 + len);
+                Ok(Some(Message::GetTransactions { ids }))
             }
             5 => {
                 info!(target: "decoding_event", "decoding transactions");
@@ -208,17 +324,22 @@ impl Decoder for MessageCodec {
                     None => return Ok(None),
                 };
                 let payload_len = usize::from(payload_len_vi);
+                if payload_len > MAX_TXS_PAYLOAD_LEN {
+                    return Err(MalformedMessageError.into());
+                }
 
                 if buf.remaining() < payload_len {
                     return Ok(None);
                 }
 
-                let mut txs = vec![]; // TODO: Estimate of size here?
-
-                while buf.remaining() > 0 {
-                    let (tx, _) = match Transaction::parse_buf(&mut buf)? {
+                // Transactions are confined to the declared payload
+                let start = 1 + payload_len_len;
+                let mut payload = Bytes::from(&src[start..start + payload_len]).into_buf();
+                let mut txs = vec![];
+                while payload.has_remaining() {
+                    let (tx, _) = match Transaction::parse_buf(&mut payload)? {
                         Some(some) => some,
-                        None => return Ok(None),
+                        None => return Err(MalformedMessageError.into()),
                     };
                     txs.push(tx);
                     info!(target: "decoding_event", "decoded transaction");
@@ -243,29 +364,71 @@ impl Decoder for MessageCodec {
                 src.advance(1);
                 Ok(Some(Message::GetWork))
             }
-            90 => {
+            9 => {
                 info!(target: "decoding_event", "decoding peers");
-                let (vi_n, _) = match VarInt::parse_buf(&mut buf)? {
+                let (peers, len) = match Peers::parse_buf(&mut buf)? {
                     None => return Ok(None),
                     Some(some) => some,
                 };
 
-                let n = usize::from(vi_n);
-                if buf.remaining() < 6 * n {
-                    return Ok(None);
-                }
+                src.advance(1 + len);
+                Ok(Some(Message::Peers { peers }))
+            }
+            10 => {
+                info!(target: "decoding_event", "decoding get peers");
+                src.advance(1);
+                Ok(Some(Message::GetPeers))
+            }
+            11 => {
+                info!(target: "decoding_event", "decoding version");
+                let (version_vi, version_len) = match VarInt::parse_buf(&mut buf)? {
+                    Some(some) => some,
+                    None => return Ok(None),
+                };
+                let (features_vi, features_len) = match VarInt::parse_buf(&mut buf)? {
+                    Some(some) => some,
+                    None => return Ok(None),
+                };
 
-                let mut vec_peers = vec![];
-                for i in 0..n {
-                    let mut dst = vec![0; 6];
-                    buf.copy_to_slice(&mut dst);
-                    vec_peers.push(Peer::from(Bytes::from(&dst[..])));
-                }
+                src.advance(1 + version_len + features_len);
+                Ok(Some(Message::Version {
+                    version: u64::from(version_vi),
+                    features: u64::from(features_vi),
+                }))
+            }
+            12 => {
+                info!(target: "decoding_event", "decoding ping");
+                let (nonce_vi, len) = match VarInt::parse_buf(&mut buf)? {
+                    Some(some) => some,
+                    None => return Ok(None),
+                };
 
-                Ok(Some(Message::Peers {
-                    peers: Peers::new(vec_peers),
+                src.advance(1 + len);
+                Ok(Some(Message::Ping {
+                    nonce: u64::from(nonce_vi),
                 }))
             }
+            13 => {
+                info!(target: "decoding_event", "decoding pong");
+                let (nonce_vi, len) = match VarInt::parse_buf(&mut buf)? {
+                    Some(some) => some,
+                    None => return Ok(None),
+                };
+
+                src.advance(1 + len);
+                Ok(Some(Message::Pong {
+                    nonce: u64::from(nonce_vi),
+                }))
+            }
+            14 => {
+                info!(target: "decoding_event", "decoding inventory");
+                let (ids, len) = match parse_ids(&mut buf, MAX_INVENTORY_PER_MSG)? {
+                    Some(some) => some,
+                    None => return Ok(None),
+                };
+                src.advance(1 + len);
+                Ok(Some(Message::Inventory { ids }))
+            }
             _ => {
                 // TODO: Remove malformed msgs
                 info!(target: "decoding_event",
@@ -1,3 +1,8 @@
+pub mod addresses;
+pub mod bans;
+pub mod connections;
 pub mod heartbeats;
 pub mod messages;
 pub mod peers;
+pub mod relay;
+pub mod transport;
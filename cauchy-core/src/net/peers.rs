@@ -2,6 +2,11 @@ use std::net::SocketAddr;
 
 use rand::seq::SliceRandom;
 
+// Address encoding versions
+pub const ADDR_V4: u8 = 4; // 4 || IPv4 || Port
+pub const ADDR_V6: u8 = 6; // 6 || IPv6 || Port
+pub const MAX_ADDR_LEN: usize = 19;
+
 #[derive(Clone)]
 pub struct Peer {
     addr: SocketAddr,
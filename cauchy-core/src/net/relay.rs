@@ -0,0 +1,122 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::sync::mpsc::Receiver;
+use tokio::prelude::*;
+use tokio::timer::Interval;
+
+use crate::{
+    db::{mongodb::MongoDB, storing::Storable},
+    primitives::{arena::Arena, transaction::Transaction},
+    stage::events::StageEvent,
+    utils::constants::{CONFIG, MAX_TXS_PAYLOAD_LEN},
+};
+
+// Bounded set of transaction ids, forgetting the oldest when full
+pub struct InventorySet {
+    ids: HashSet<Bytes>,
+    order: VecDeque<Bytes>,
+    capacity: usize,
+}
+
+impl InventorySet {
+    pub fn with_capacity(capacity: usize) -> InventorySet {
+        InventorySet {
+            ids: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: &Bytes) -> bool {
+        self.ids.contains(id)
+    }
+
+    // Returns false if already present
+    pub fn insert(&mut self, id: Bytes) -> bool {
+        if self.capacity == 0 || !self.ids.insert(id.clone()) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+// Ids of announced transactions not yet stored
+pub fn unstored(db: &MongoDB, ids: HashSet<Bytes>) -> HashSet<Bytes> {
+    ids.into_iter()
+        .filter(|id| {
+            Transaction::from_db(&mut db.clone(), id.clone()).map_or(false, |tx| tx.is_none())
+        })
+        .collect()
+}
+
+// Stored transactions among those requested, paired with their ids
+pub fn fetch_stored(db: &MongoDB, ids: Vec<Bytes>) -> Vec<(Bytes, Transaction)> {
+    ids.into_iter()
+        .filter_map(|id| {
+            let tx = Transaction::from_db(&mut db.clone(), id.clone()).ok()??;
+            Some((id, tx))
+        })
+        .collect()
+}
+
+// Split transactions into messages within the payload limit, dropping any too large alone
+pub fn batch_transactions(txs: Vec<Transaction>) -> Vec<Vec<Transaction>> {
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut batch_len = 0;
+    for tx in txs {
+        let tx_len = Bytes::from(tx.clone()).len();
+        if tx_len > MAX_TXS_PAYLOAD_LEN {
+            continue;
+        }
+        if batch_len + tx_len > MAX_TXS_PAYLOAD_LEN {
+            batches.push(std::mem::replace(&mut batch, vec![]));
+            batch_len = 0;
+        }
+        batch_len += tx_len;
+        batch.push(tx);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+// Announce transactions to peers once they are stored
+pub fn relay(
+    arena: Arc<Mutex<Arena>>,
+    events: Receiver<StageEvent>,
+) -> impl Future<Item = (), Error = ()> {
+    let arena_inner = arena.clone();
+    let queue = events.for_each(move |event| {
+        if let StageEvent::Performed { tx_id, .. } = event {
+            arena_inner.lock().unwrap().queue_announcement(tx_id);
+        }
+        Ok(())
+    });
+
+    let announce = Interval::new_interval(CONFIG.relay.announce_interval_ms)
+        .map_err(|_| ()) // TODO: Catch?
+        .for_each(move |_| {
+            arena.lock().unwrap().inventory_pulse();
+            Ok(())
+        });
+
+    queue.join(announce).map(|_| ())
+}
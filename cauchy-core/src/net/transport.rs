@@ -0,0 +1,240 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use failure::Error;
+use log::info;
+use secp256k1::key::{PublicKey, SecretKey};
+use tokio::codec::{Decoder, Encoder};
+use tokio::io::{read_exact, write_all};
+use tokio::net::TcpStream;
+use tokio::prelude::*;
+use tokio::timer::Timeout;
+
+use super::messages::{max_message_len, Message, MessageCodec, NegotiatedVersion, MAX_MESSAGE_TAG};
+use crate::{
+    crypto::{hashes::blake2b::Blk2bHashable, signatures::ecdsa::*},
+    primitives::varint::VarInt,
+    utils::{
+        constants::{CONFIG, MAX_FRAME_LEN, PUBKEY_LEN},
+        errors::{MalformedMessageError, TransportError},
+    },
+};
+
+const SESSION_DOMAIN: &[u8] = b"cauchy-session";
+const FRAME_HEADER_LEN: usize = 4;
+
+// One direction of a session, nonces are never reused under a key
+pub struct CipherState {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl CipherState {
+    pub fn new(key: &[u8]) -> CipherState {
+        CipherState {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            counter: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> Result<[u8; 12], Error> {
+        let mut nonce = [0; 12];
+        nonce[4..].copy_from_slice(&self.counter.to_le_bytes());
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or(TransportError::NonceExhausted)?;
+        Ok(nonce)
+    }
+
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = self.next_nonce()?;
+        self.cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| TransportError::Encryption.into())
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = self.next_nonce()?;
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .map_err(|_| TransportError::Decryption.into())
+    }
+}
+
+// Frames are Length u32 || Encrypted message || Tag
+pub struct SecureCodec {
+    inner: MessageCodec,
+    send: CipherState,
+    recv: CipherState,
+}
+
+impl SecureCodec {
+    pub fn new(send: CipherState, recv: CipherState) -> SecureCodec {
+        SecureCodec {
+            inner: MessageCodec::default(),
+            send,
+            recv,
+        }
+    }
+
+    pub fn get_version(&self) -> NegotiatedVersion {
+        self.inner.get_version()
+    }
+}
+
+impl Encoder for SecureCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut plaintext = BytesMut::new();
+        self.inner.encode(item, &mut plaintext)?;
+        check_message_len(&plaintext)?;
+        let ciphertext = self.send.encrypt(&plaintext)?;
+        if ciphertext.len() > MAX_FRAME_LEN {
+            return Err(TransportError::FrameTooLarge {
+                len: ciphertext.len(),
+            }
+            .into());
+        }
+        dst.reserve(FRAME_HEADER_LEN + ciphertext.len());
+        dst.put_u32_be(ciphertext.len() as u32);
+        dst.extend_from_slice(&ciphertext);
+        Ok(())
+    }
+}
+
+impl SecureCodec {
+    fn next_frame(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, Error> {
+        if src.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(TransportError::FrameTooLarge { len }.into());
+        }
+        if src.len() < FRAME_HEADER_LEN + len {
+            src.reserve(FRAME_HEADER_LEN + len - src.len());
+            return Ok(None);
+        }
+        src.advance(FRAME_HEADER_LEN);
+        let ciphertext = src.split_to(len);
+        Ok(Some(BytesMut::from(self.recv.decrypt(&ciphertext)?)))
+    }
+}
+
+impl Decoder for SecureCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        while let Some(mut plaintext) = self.next_frame(src)? {
+            // Messages added by newer versions are skipped
+            match plaintext.first() {
+                Some(&tag) if tag > MAX_MESSAGE_TAG => {
+                    info!(target: "decoding_event", "skipping unknown message {}", tag);
+                    continue;
+                }
+                Some(_) => check_message_len(&plaintext)?,
+                None => return Err(MalformedMessageError.into()),
+            }
+
+            // A frame carries exactly one message
+            return match self.inner.decode(&mut plaintext)? {
+                Some(msg) if plaintext.is_empty() => Ok(Some(msg)),
+                _ => Err(MalformedMessageError.into()),
+            };
+        }
+        Ok(None)
+    }
+}
+
+fn check_message_len(plaintext: &[u8]) -> Result<(), Error> {
+    let tag = plaintext[0];
+    if plaintext.len() > max_message_len(tag) {
+        return Err(TransportError::MessageTooLarge {
+            tag,
+            len: plaintext.len(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+pub struct Session {
+    pub id: Bytes,
+    pub codec: SecureCodec,
+}
+
+impl Session {
+    // Both sides order the ephemeral keys identically to agree on who sends under which key
+    pub fn new(
+        ephemeral_sk: &SecretKey,
+        ephemeral_pk: &PublicKey,
+        remote_pk: &PublicKey,
+    ) -> Result<Session, Error> {
+        let local_raw = bytes_from_pubkey(*ephemeral_pk);
+        let remote_raw = bytes_from_pubkey(*remote_pk);
+        if local_raw == remote_raw {
+            return Err(TransportError::ReflectedKey.into());
+        }
+        let low_side = local_raw < remote_raw;
+        let (low, high) = if low_side {
+            (local_raw, remote_raw)
+        } else {
+            (remote_raw, local_raw)
+        };
+
+        let mut preimage = BytesMut::new();
+        preimage.extend_from_slice(SESSION_DOMAIN);
+        preimage.extend_from_slice(&shared_secret(remote_pk, ephemeral_sk));
+        preimage.extend_from_slice(&low);
+        preimage.extend_from_slice(&high);
+        let id = preimage.freeze().blake2b();
+
+        let low_key = derive_key(&id, 0);
+        let high_key = derive_key(&id, 1);
+        let (send_key, recv_key) = if low_side {
+            (low_key, high_key)
+        } else {
+            (high_key, low_key)
+        };
+        Ok(Session {
+            id,
+            codec: SecureCodec::new(CipherState::new(&send_key), CipherState::new(&recv_key)),
+        })
+    }
+}
+
+fn derive_key(session_id: &Bytes, direction: u8) -> Bytes {
+    let mut preimage = BytesMut::from(&session_id[..]);
+    preimage.put_u8(direction);
+    preimage.freeze().blake2b()
+}
+
+// Signed during the identity handshake, binding the node key to this session
+pub fn handshake_preimage(secret: u64, session_id: &Bytes) -> Bytes {
+    let mut preimage = BytesMut::from(Bytes::from(VarInt::new(secret)));
+    preimage.extend_from_slice(session_id);
+    preimage.freeze()
+}
+
+// Exchange ephemeral keys before any message is sent, giving up on silent peers
+pub fn handshake(socket: TcpStream) -> impl Future<Item = (TcpStream, Session), Error = Error> {
+    let (ephemeral_sk, ephemeral_pk) = generate_keypair();
+    let exchange = write_all(socket, bytes_from_pubkey(ephemeral_pk))
+        .and_then(|(socket, _)| read_exact(socket, vec![0; PUBKEY_LEN]))
+        .map_err(Error::from)
+        .and_then(move |(socket, remote_raw)| {
+            let remote_pk = pubkey_from_bytes(Bytes::from(remote_raw))?;
+            let session = Session::new(&ephemeral_sk, &ephemeral_pk, &remote_pk)?;
+            Ok((socket, session))
+        });
+    Timeout::new(exchange, CONFIG.timeouts.handshake_ms).map_err(|err| {
+        err.into_inner()
+            .unwrap_or_else(|| TransportError::HandshakeTimeout.into())
+    })
+}
@@ -6,7 +6,7 @@ use bytes::Bytes;
 
 use crate::utils::byte_ops::*;
 
-#[derive(Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct AccessPattern {
     pub read: HashSet<Bytes>,
     pub write: HashMap<Bytes, Bytes>,
@@ -4,14 +4,15 @@ use bytes::Bytes;
 
 use super::access_pattern::*;
 
-#[derive(Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Act {
     pub access_pattern: AccessPattern,
     messages: Vec<Message>,
+    events: Vec<Event>,
     operations: u64,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Message {
     sender: Bytes,
     receiver: Bytes,
@@ -40,10 +41,63 @@ impl Message {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Event {
+    topic: Bytes,
+    data: Bytes,
+}
+
+impl Event {
+    pub fn new(topic: Bytes, data: Bytes) -> Event {
+        Event { topic, data }
+    }
+
+    pub fn get_topic(&self) -> Bytes {
+        self.topic.clone()
+    }
+
+    pub fn get_data(&self) -> Bytes {
+        self.data.clone()
+    }
+}
+
+impl Act {
+    pub fn new(
+        access_pattern: AccessPattern,
+        messages: Vec<Message>,
+        events: Vec<Event>,
+        operations: u64,
+    ) -> Act {
+        Act {
+            access_pattern,
+            messages,
+            events,
+            operations,
+        }
+    }
+
+    pub fn get_messages(&self) -> Vec<Message> {
+        self.messages.clone()
+    }
+
+    pub fn get_events(&self) -> Vec<Event> {
+        self.events.clone()
+    }
+
+    pub fn add_event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn get_operations(&self) -> u64 {
+        self.operations
+    }
+}
+
 impl AddAssign for Act {
     fn add_assign(&mut self, other: Act) {
         self.access_pattern += other.access_pattern;
         self.messages.extend(other.messages);
+        self.events.extend(other.events);
         self.operations += other.operations;
     }
 }
@@ -1,26 +1,46 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
+use bytes::Bytes;
 use log::info;
-use rand::seq::SliceRandom;
+use rand::Rng;
 use secp256k1::PublicKey;
 
 use crate::{
     crypto::sketches::odd_sketch::OddSketch,
     ego::{ego::*, peer_ego::*, *},
-    net::{messages::Message, peers::Peer},
+    net::{
+        addresses::{address_group, AddressManager, AddressRecord},
+        bans::{BanList, Misbehaviour, ScoreBook},
+        messages::Message,
+        peers::{Peer, Peers},
+    },
     primitives::{
         status::*,
         work::{WorkSite, WorkStack, WorkState},
     },
-    utils::constants::CONFIG,
+    utils::{
+        constants::{Timeouts, CONFIG, PING_VERSION, RELAY_VERSION},
+        timing::{duration_from_millis, get_current_time},
+    },
 };
 
 pub struct Arena {
     ego: Arc<Mutex<Ego>>,
     peer_egos: HashMap<SocketAddr, Arc<Mutex<PeerEgo>>>,
+    outbound: HashSet<SocketAddr>,
+    pending: HashSet<SocketAddr>, // Outbound dials in progress
+    handshakes: usize,            // Inbound transport handshakes in progress
+    bans: BanList,
+    scores: ScoreBook,
+    addresses: AddressManager,
+    timeouts: HashMap<&'static str, u64>, // Number of timeouts by status
+    relay_queue: Vec<Bytes>,              // Stored transactions awaiting announcement
+    requests: HashMap<Bytes, Instant>,    // Transactions requested from peers
 }
 
 impl Arena {
@@ -28,6 +48,15 @@ impl Arena {
         Arena {
             ego,
             peer_egos: HashMap::new(),
+            outbound: HashSet::new(),
+            pending: HashSet::new(),
+            handshakes: 0,
+            bans: BanList::default(),
+            scores: ScoreBook::new(CONFIG.banning.decay_ms),
+            addresses: AddressManager::with_capacity(CONFIG.discovery.max_addresses),
+            timeouts: HashMap::new(),
+            relay_queue: vec![],
+            requests: HashMap::new(),
         }
     }
 
@@ -35,33 +64,335 @@ impl Arena {
         self.ego.clone()
     }
 
-    pub fn new_peer(&mut self, addr: &SocketAddr, peer_ego: Arc<Mutex<PeerEgo>>) {
+    pub fn new_peer(&mut self, addr: &SocketAddr, peer_ego: Arc<Mutex<PeerEgo>>, outbound: bool) {
         info!(target: "arena_event", "added {} to arena", addr);
+        peer_ego
+            .lock()
+            .unwrap()
+            .raise_score(self.scores.score(addr, None));
         self.peer_egos.insert(*addr, peer_ego);
+        if outbound {
+            self.pending.remove(addr);
+            self.outbound.insert(*addr);
+        }
     }
 
     pub fn remove_peer(&mut self, addr: &SocketAddr) {
         info!(target: "arena_event", "removed {} from arena", addr);
-        self.peer_egos.remove(addr);
+        self.outbound.remove(addr);
+        let peer_ego = match self.peer_egos.remove(addr) {
+            Some(some) => some,
+            None => return,
+        };
+
+        // Misbehaviour outlives the connection, banning repeat offenders
+        let peer_ego_guard = peer_ego.lock().unwrap();
+        let pubkey = peer_ego_guard.get_pubkey();
+        self.scores.record(addr, pubkey, peer_ego_guard.get_score());
+        if peer_ego_guard.is_banned() {
+            self.ban(addr, pubkey);
+        }
     }
 
-    pub fn work_pulse(&self, size: usize) {
+    // Score carried over from earlier connections by the address or key
+    pub fn get_score(&self, addr: &SocketAddr, pubkey: Option<&PublicKey>) -> u32 {
+        self.scores.score(addr, pubkey)
+    }
+
+    pub fn ban(&mut self, addr: &SocketAddr, pubkey: Option<PublicKey>) {
+        self.bans.ban(addr, pubkey, CONFIG.banning.ban_ms);
+        let until = get_current_time() + CONFIG.banning.ban_ms.as_millis() as u64;
+        self.addresses.record_ban(*addr, pubkey, until);
+    }
+
+    pub fn is_banned(&mut self, addr: &SocketAddr) -> bool {
+        self.bans.is_banned(addr)
+    }
+
+    pub fn is_banned_pubkey(&mut self, pubkey: &PublicKey) -> bool {
+        self.bans.is_banned_pubkey(pubkey)
+    }
+
+    pub fn n_peers(&self) -> usize {
+        self.peer_egos.len()
+    }
+
+    // Includes dials in progress
+    pub fn n_outbound(&self) -> usize {
+        self.outbound.len() + self.pending.len()
+    }
+
+    // Includes handshakes in progress
+    pub fn n_inbound(&self) -> usize {
+        self.peer_egos.len() - self.outbound.len() + self.handshakes
+    }
+
+    pub fn start_handshake(&mut self) {
+        self.handshakes += 1;
+    }
+
+    pub fn end_handshake(&mut self) {
+        self.handshakes = self.handshakes.saturating_sub(1);
+    }
+
+    pub fn n_addresses(&self) -> usize {
+        self.addresses.len()
+    }
+
+    // Restore the address book and its outstanding bans
+    pub fn load_addresses(&mut self, records: Vec<AddressRecord>) {
+        info!(target: "arena_event", "loaded {} addresses", records.len());
+        let now = get_current_time();
+        for record in records {
+            if let Some(banned_until) = record.banned_until {
+                if banned_until > now {
+                    let remaining = duration_from_millis(banned_until - now);
+                    self.bans.ban(&record.addr, record.pubkey, remaining);
+                }
+            }
+            self.addresses.insert_record(record);
+        }
+    }
+
+    pub fn take_dirty_addresses(&mut self) -> (Vec<AddressRecord>, Vec<SocketAddr>) {
+        self.addresses.take_dirty()
+    }
+
+    pub fn record_connection(&mut self, addr: SocketAddr, pubkey: PublicKey) {
+        self.addresses.record_success(addr, pubkey);
+    }
+
+    pub fn dial_failed(&mut self, addr: SocketAddr) {
+        self.pending.remove(&addr);
+        self.addresses
+            .record_failure(addr, CONFIG.discovery.max_failures);
+    }
+
+    // Dial abandoned through no fault of the address
+    pub fn dial_aborted(&mut self, addr: &SocketAddr) {
+        self.pending.remove(addr);
+    }
+
+    pub fn learn_addresses(&mut self, peers: Peers, source: &SocketAddr) {
+        self.addresses.insert_batch(peers, source);
+    }
+
+    pub fn forget_address(&mut self, addr: &SocketAddr) {
+        self.addresses.remove(addr);
+    }
+
+    pub fn sample_addresses(&self, n: usize) -> Peers {
+        self.addresses.sample_n(n)
+    }
+
+    // Known addresses which are neither connected nor banned, marked as pending
+    pub fn dial_candidates(&mut self, n: usize) -> Vec<SocketAddr> {
+        let exclude: HashSet<SocketAddr> = self
+            .peer_egos
+            .keys()
+            .chain(self.pending.iter())
+            .cloned()
+            .collect();
+        let groups: HashSet<Vec<u8>> = self
+            .outbound
+            .iter()
+            .chain(self.pending.iter())
+            .filter_map(address_group)
+            .collect();
+        let backoff = (
+            CONFIG.connections.backoff_base_ms.as_millis() as u64,
+            CONFIG.connections.backoff_max_ms.as_millis() as u64,
+        );
+        let candidates: Vec<SocketAddr> = self
+            .addresses
+            .dial_candidates(n, &exclude, &groups, backoff)
+            .into_iter()
+            .filter(|addr| !self.bans.is_banned(addr))
+            .collect();
+
+        let now = get_current_time();
+        for addr in &candidates {
+            self.pending.insert(*addr);
+            self.addresses.record_attempt(*addr, now);
+        }
+        candidates
+    }
+
+    pub fn peers_pulse(&self) {
         self.peer_egos
             .values()
-            .filter_map(|peer_ego| {
-                // Select from only those with a public key
-                let peer_ego_guard = peer_ego.lock().unwrap();
-                if peer_ego_guard.get_pubkey().is_some() {
-                    Some(peer_ego_guard)
-                } else {
-                    None
-                }
-            })
-            .take(size) // TODO: Shuffle before taking
+            .map(|peer_ego| peer_ego.lock().unwrap())
+            .filter(|peer_ego_guard| peer_ego_guard.get_pubkey().is_some())
             .for_each(|mut peer_ego_guard| {
-                peer_ego_guard.update_status(PeerStatus::WorkPull);
-                peer_ego_guard.send_msg(Message::GetWork);
+                peer_ego_guard.request_peers();
+                peer_ego_guard.send_msg(Message::GetPeers)
+            })
+    }
+
+    // Reset peers, and ego, stuck waiting on a response
+    pub fn timeout_sweep(&mut self, timeouts: &Timeouts) {
+        let mut expired = vec![];
+        let mut abandoned = false;
+        let mut ingesting = false;
+        for (addr, peer_ego) in self.peer_egos.iter() {
+            let mut peer_ego_guard = peer_ego.lock().unwrap();
+            let status = peer_ego_guard.get_status();
+            let (timeout, misbehaviour) = match status {
+                PeerStatus::WorkPull => (timeouts.work_pull_ms, Some(Misbehaviour::WorkTimeout)),
+                // The payload arrived, ingest resets the status
+                PeerStatus::StatePull(ref expectation) if expectation.is_received() => {
+                    ingesting = true;
+                    continue;
+                }
+                PeerStatus::StatePull(_) => {
+                    (timeouts.state_pull_ms, Some(Misbehaviour::ReconcileTimeout))
+                }
+                PeerStatus::StatePush => (timeouts.state_push_ms, None),
+                _ => continue,
+            };
+            if peer_ego_guard.get_status_elapsed() < timeout {
+                continue;
+            }
+
+            info!(target: "arena_event", "{} timed out while {}", addr, status.to_str());
+            if let Some(misbehaviour) = misbehaviour {
+                peer_ego_guard.penalise(misbehaviour);
+            }
+            peer_ego_guard.update_status(PeerStatus::Idle);
+
+            if let PeerStatus::StatePull(_) = status {
+                abandoned = true;
+            }
+            expired.push(status.to_str());
+        }
+
+        // Locked only once no peer ego is, keeping the ego before peer ego lock order
+        let mut ego_guard = self.ego.lock().unwrap();
+
+        // Abandon reconciliation with the peer
+        if abandoned {
+            ego_guard.update_status(Status::Idle);
+        }
+        if !ingesting
+            && ego_guard.get_status() == Status::Pulling
+            && ego_guard.get_status_elapsed() >= timeouts.pulling_ms
+        {
+            info!(target: "arena_event", "timed out while pulling");
+            ego_guard.update_status(Status::Idle);
+            expired.push(Status::Pulling.to_str());
+        }
+
+        for status in expired {
+            let count = self.timeouts.entry(status).or_insert(0);
+            *count += 1;
+            info!(target: "arena_event", "{} timeouts while {}", count, status);
+        }
+    }
+
+    pub fn get_timeouts(&self) -> HashMap<&'static str, u64> {
+        self.timeouts.clone()
+    }
+
+    pub fn queue_announcement(&mut self, tx_id: Bytes) {
+        self.relay_queue.push(tx_id);
+    }
+
+    // Announce newly stored transactions to peers relaying inventory
+    pub fn inventory_pulse(&mut self) {
+        let now = Instant::now();
+        self.requests.retain(|_, requested| {
+            now.duration_since(*requested) < CONFIG.relay.request_timeout_ms
+        });
+        for peer_ego in self.peer_egos.values() {
+            peer_ego
+                .lock()
+                .unwrap()
+                .expire_requests(CONFIG.relay.request_timeout_ms);
+        }
+        if self.relay_queue.is_empty() {
+            return;
+        }
+
+        let ids = std::mem::replace(&mut self.relay_queue, vec![]);
+        info!(target: "arena_event", "announcing {} transactions", ids.len());
+        for peer_ego in self.peer_egos.values() {
+            let mut peer_ego_guard = peer_ego.lock().unwrap();
+            if peer_ego_guard.get_pubkey().is_some()
+                && peer_ego_guard
+                    .get_version()
+                    .map_or(false, |version| version >= RELAY_VERSION)
+            {
+                peer_ego_guard.announce(&ids);
+            }
+        }
+    }
+
+    // Whether a transaction is awaiting a reply from some peer
+    pub fn is_requested(&self, id: &Bytes) -> bool {
+        self.requests.get(id).map_or(false, |time| {
+            time.elapsed() < CONFIG.relay.request_timeout_ms
+        })
+    }
+
+    // Transactions not already requested from another peer, recorded as requested
+    pub fn request_transactions(&mut self, ids: HashSet<Bytes>) -> HashSet<Bytes> {
+        let now = Instant::now();
+        let mut requested = HashSet::with_capacity(ids.len());
+        for id in ids {
+            if !self.is_requested(&id) {
+                self.requests.insert(id.clone(), now);
+                requested.insert(id);
+            }
+        }
+        requested
+    }
+
+    // Disconnect peers that missed a keepalive and ping the rest
+    pub fn keepalive_pulse(&self) {
+        for (addr, peer_ego) in self.peer_egos.iter() {
+            let mut peer_ego_guard = peer_ego.lock().unwrap();
+            match peer_ego_guard.get_version() {
+                _ if peer_ego_guard.is_unresponsive(CONFIG.keepalive.ping_timeout_ms) => {
+                    info!(target: "arena_event", "evicting unresponsive peer {}", addr);
+                    peer_ego_guard.disconnect();
+                }
+                // Peers unable to answer pings are judged by their last message
+                Some(version) if version < PING_VERSION => {
+                    if peer_ego_guard.is_silent(CONFIG.keepalive.idle_timeout_ms) {
+                        info!(target: "arena_event", "evicting silent peer {}", addr);
+                        peer_ego_guard.disconnect();
+                    }
+                }
+                Some(_) => peer_ego_guard.ping(),
+                None => (),
+            }
+        }
+    }
+
+    pub fn work_pulse(&self, size: usize) {
+        // Select from only those with a public key
+        let peer_ego_guards: Vec<MutexGuard<PeerEgo>> = self
+            .peer_egos
+            .values()
+            .map(|peer_ego| peer_ego.lock().unwrap())
+            .filter(|peer_ego_guard| peer_ego_guard.get_pubkey().is_some())
+            .collect();
+
+        // Prefer peers with low round trip times, unmeasured peers count as timing out
+        let candidates = peer_ego_guards
+            .into_iter()
+            .map(|peer_ego_guard| {
+                let rtt = peer_ego_guard
+                    .get_rtt()
+                    .unwrap_or(CONFIG.keepalive.ping_timeout_ms);
+                (rtt, peer_ego_guard)
             })
+            .collect();
+
+        for mut peer_ego_guard in sample_by_rtt(candidates, size) {
+            peer_ego_guard.update_status(PeerStatus::WorkPull);
+            peer_ego_guard.send_msg(Message::GetWork);
+        }
     }
 
     pub fn reconcile_leader(&self) {
@@ -101,6 +432,7 @@ impl Arena {
             .mine(ego_guard.work_stack.get_oddsketch());
 
         info!(target: "arena_event", "self distance: {}", best_dist);
+        let self_dist = best_dist;
 
         // Calculate peer distance
         for (i, (_, work_stack, _)) in profiles.iter().enumerate() {
@@ -127,8 +459,9 @@ impl Arena {
                 for (j, (peer_ego, work_stack, _)) in profiles.iter_mut().enumerate() {
                     if i == j {
                         // Update status to pulling with expectation grabbed from current status
-                        let expectation =
+                        let mut expectation =
                             Expectation::new(work_stack.get_oddsketch(), work_stack.get_root());
+                        expectation.update_leader(best_dist < self_dist);
                         peer_ego.update_status(PeerStatus::StatePull(expectation));
                         ego_guard.update_status(Status::Pulling);
 
@@ -152,3 +485,24 @@ impl Arena {
         }
     }
 }
+
+// Sample without replacement, the chance of selection falls with round trip time
+pub fn sample_by_rtt<T>(candidates: Vec<(Duration, T)>, size: usize) -> Vec<T> {
+    let mut rng = rand::thread_rng();
+
+    // Exponential keys, weighting each candidate by its inverse round trip time in ms
+    let mut keyed: Vec<(f64, T)> = candidates
+        .into_iter()
+        .map(|(rtt, candidate)| {
+            let rtt_ms = rtt.as_secs() as f64 * 1000.0 + f64::from(rtt.subsec_millis()) + 1.0;
+            let uniform = 1.0 - rng.gen::<f64>();
+            (uniform.ln() * rtt_ms, candidate)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    keyed
+        .into_iter()
+        .take(size)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
@@ -0,0 +1,97 @@
+use bytes::Bytes;
+use failure::Error;
+
+use crate::{
+    crypto::hashes::blake2b::Blk2bHashable,
+    utils::{constants::HASH_LEN, errors::CallError},
+};
+
+/*
+    Selector    ||    u8    ||    (Tag || Argument) ...
+       ^ u32 LE       ^ Number of arguments
+
+    Fixed width integers and lengths are little-endian so that scripts can
+    read them in place.
+
+    Tag     Argument
+    0       u32
+    1       u64
+    2       u32 length || Bytes
+    3       Actor ID (HASH_LEN bytes)
+*/
+
+pub const MAX_ARGUMENTS: usize = 255;
+
+pub const ARG_U32: u8 = 0;
+pub const ARG_U64: u8 = 1;
+pub const ARG_BYTES: u8 = 2;
+pub const ARG_ID: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Argument {
+    U32(u32),
+    U64(u64),
+    Bytes(Bytes),
+    Id(Bytes),
+}
+
+impl Argument {
+    pub fn get_tag(&self) -> u8 {
+        match self {
+            Argument::U32(_) => ARG_U32,
+            Argument::U64(_) => ARG_U64,
+            Argument::Bytes(_) => ARG_BYTES,
+            Argument::Id(_) => ARG_ID,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    selector: u32,
+    arguments: Vec<Argument>,
+}
+
+// The selector of a method is the first four bytes of the hash of its name
+pub fn selector(method: &str) -> u32 {
+    let digest = Bytes::from(method.as_bytes()).blake2b();
+    let mut raw = [0; 4];
+    raw.copy_from_slice(&digest[..4]);
+    u32::from_le_bytes(raw)
+}
+
+impl Call {
+    pub fn new(method: &str, arguments: Vec<Argument>) -> Result<Call, Error> {
+        Call::from_selector(selector(method), arguments)
+    }
+
+    pub fn from_selector(selector: u32, arguments: Vec<Argument>) -> Result<Call, Error> {
+        if arguments.len() > MAX_ARGUMENTS {
+            return Err(CallError::TooManyArguments { n: arguments.len() }.into());
+        }
+        // Ids are encoded without a length
+        for argument in &arguments {
+            if let Argument::Id(id) = argument {
+                if id.len() != HASH_LEN {
+                    return Err(CallError::InvalidIdLength { len: id.len() }.into());
+                }
+            }
+        }
+        Ok(Call {
+            selector,
+            arguments,
+        })
+    }
+
+    pub fn get_selector(&self) -> u32 {
+        self.selector
+    }
+
+    pub fn get_arguments(&self) -> &[Argument] {
+        &self.arguments
+    }
+
+    pub fn is_method(&self, method: &str) -> bool {
+        self.selector == selector(method)
+    }
+}
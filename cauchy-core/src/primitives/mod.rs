@@ -1,6 +1,7 @@
 pub mod access_pattern;
 pub mod act;
 pub mod arena;
+pub mod call;
 pub mod status;
 pub mod transaction;
 pub mod tx_pool;
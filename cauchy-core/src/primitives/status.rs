@@ -18,6 +18,8 @@ pub struct Expectation {
     root: Bytes,
     ids: Option<HashSet<Bytes>>,
     minisketch: Option<DummySketch>, // Post reconciliation our minisketch should match this
+    leader: bool,                    // Peer strictly beat our own distance
+    received: bool,                  // Payload arrived and awaits ingest
 }
 
 impl Expectation {
@@ -27,6 +29,8 @@ impl Expectation {
             root,
             ids: None,
             minisketch: None,
+            leader: false,
+            received: false,
         }
     }
 
@@ -42,6 +46,26 @@ impl Expectation {
         self.minisketch.clone()
     }
 
+    pub fn get_root(&self) -> Bytes {
+        self.root.clone()
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.leader
+    }
+
+    pub fn update_leader(&mut self, leader: bool) {
+        self.leader = leader
+    }
+
+    pub fn is_received(&self) -> bool {
+        self.received
+    }
+
+    pub fn mark_received(&mut self) {
+        self.received = true
+    }
+
     pub fn update_ids(&mut self, ids: HashSet<Bytes>) {
         self.ids = Some(ids)
     }
@@ -54,6 +78,10 @@ impl Expectation {
         Some(transactions.iter().map(|tx| tx.get_id()).collect()) == self.ids
     }
 
+    pub fn is_expected_ids(&self, ids: &[Bytes]) -> bool {
+        Some(ids.iter().cloned().collect()) == self.ids
+    }
+
     pub fn clear_ids(&mut self) {
         self.ids = None
     }
@@ -84,6 +84,29 @@ impl TxPool {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    // Remove and return transactions timestamped at or before time, in order
+    pub fn drain_ready(&mut self, time: u64) -> Vec<Transaction> {
+        let items = std::mem::replace(&mut self.txs, BinaryHeap::with_capacity(self.size))
+            .into_sorted_vec();
+        let (ready, pending): (Vec<TxPoolItem>, Vec<TxPoolItem>) = items
+            .into_iter()
+            .partition(|item| item.tx.get_time() <= time);
+        self.txs.extend(pending);
+        ready.into_iter().map(|item| item.tx).collect()
+    }
+
     pub fn into_sorted_txs(self) -> Vec<Transaction> {
         self.txs
             .into_sorted_vec()
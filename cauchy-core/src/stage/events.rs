@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::{
+    crypto::sketches::odd_sketch::OddSketch, utils::constants::EVENT_CHANNEL_CAPACITY,
+    vm::performance::Performance,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rejection {
+    Duplicate,
+    InvalidPayload, // Reconciliation payload failed validation
+    Conflict,       // Reconciliation would roll back provisional state
+    Preempted,      // Reconciliation abandoned for a forced local batch
+    MempoolFull,    // No room to wait behind a reconciliation
+    StoreFailed,    // Could not be written to the tx store
+    RunFailed,      // Performance failed, removed from the tx store
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StageEvent {
+    Accepted {
+        tx_id: Bytes,
+    },
+    Rejected {
+        tx_id: Bytes,
+        reason: Rejection,
+    },
+    Performed {
+        tx_id: Bytes,
+        performance: Arc<Performance>,
+    },
+    StateChanged {
+        actor_id: Bytes,
+        key: Bytes,
+        value: Bytes,
+    },
+    Reverted {
+        tx_id: Bytes,
+    },
+    RootUpdated {
+        oddsketch: OddSketch,
+        root: Bytes,
+    },
+}
+
+// Fan out of stage events to any number of subscribers
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<StageEvent>>>>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Receiver<StageEvent> {
+        let (send, recv) = channel(EVENT_CHANNEL_CAPACITY);
+        self.subscribers.lock().unwrap().push(send);
+        recv
+    }
+
+    pub fn n_subscribers(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    // Events not delivered to lagging subscribers
+    pub fn n_dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // Subscribers with a full channel miss the event, dropped subscribers are removed
+    pub fn publish(&self, event: StageEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let live = subscribers
+            .drain(..)
+            .filter_map(|mut subscriber| match subscriber.try_send(event.clone()) {
+                Ok(()) => Some(subscriber),
+                Err(ref err) if err.is_full() => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    Some(subscriber)
+                }
+                Err(_) => None,
+            })
+            .collect();
+        *subscribers = live;
+    }
+
+    pub fn publish_performance(&self, tx_id: Bytes, performance: &Performance) {
+        for (actor_id, act) in performance.0.iter() {
+            for (key, value) in act.access_pattern.write.iter() {
+                self.publish(StageEvent::StateChanged {
+                    actor_id: actor_id.clone(),
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        // Shared between subscribers rather than cloned per event
+        self.publish(StageEvent::Performed {
+            tx_id,
+            performance: Arc::new(performance.clone()),
+        });
+    }
+}
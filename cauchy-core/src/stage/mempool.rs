@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+
+use futures::future::{ok, Either};
+use futures::sink::Sink;
+use futures::sync::mpsc::Sender;
+use futures::{Future, Stream};
+use log::{error, info};
+use tokio::timer::Interval;
+
+use super::{filter_duplicates, is_stored};
+
+use crate::{
+    daemon::{Origin, Priority},
+    db::{blocking, mongodb::MongoDB},
+    ego::ego::Ego,
+    primitives::{status::Status, transaction::Transaction, tx_pool::TxPool},
+    utils::{constants::CONFIG, timing::get_current_time},
+};
+
+// Take ready transactions from the mempool, in order, dropping those already stored
+pub fn drain(mempool: &Arc<Mutex<TxPool>>, db: &MongoDB) -> Vec<Transaction> {
+    let txs = mempool.lock().unwrap().drain_ready(get_current_time());
+    filter_duplicates(txs, |tx| is_stored(db, tx))
+}
+
+pub fn processor(
+    ego: Arc<Mutex<Ego>>,
+    mempool: Arc<Mutex<TxPool>>,
+    db: MongoDB,
+    stage_send: Sender<(Origin, TxPool, Priority)>,
+) -> impl Future<Item = (), Error = ()> + Send {
+    Interval::new_interval(CONFIG.mempool.drain_interval_ms)
+        .map_err(|_| ()) // TODO: Catch?
+        .for_each(move |_| {
+            let size = mempool.lock().unwrap().len();
+            info!(target: "stage_event", "mempool size {}", size);
+
+            // Only drain when idle
+            if size == 0 || ego.lock().unwrap().get_status() != Status::Idle {
+                return Either::A(ok(()));
+            }
+
+            // Tx store lookups run off the executor
+            let mempool = mempool.clone();
+            let db = db.clone();
+            let stage_send = stage_send.clone();
+            Either::B(blocking(move || drain(&mempool, &db)).and_then(move |txs| {
+                if txs.is_empty() {
+                    return Either::A(ok(()));
+                }
+                info!(target: "stage_event", "draining {} transactions from mempool", txs.len());
+
+                let mut tx_pool = TxPool::with_capacity(txs.len());
+                if let Err(err) = tx_pool.insert_batch(txs, false) {
+                    error!(target: "stage_event", "failed to batch mempool transactions: {}", err);
+                    return Either::A(ok(()));
+                }
+                Either::B(
+                    stage_send
+                        .send((Origin::Mempool, tx_pool, Priority::Standard))
+                        .map(|_| ())
+                        .map_err(|_| ()),
+                )
+            }))
+        })
+}
@@ -1,3 +1,6 @@
+pub mod events;
+pub mod mempool;
+
 use std::collections::{HashMap, HashSet};
 use std::ops::AddAssign;
 use std::sync::{Arc, Mutex};
@@ -5,25 +8,36 @@ use std::sync::{Arc, Mutex};
 use bus::Bus;
 use bytes::{Bytes, BytesMut};
 use failure::Error;
-use futures::future::{err, ok};
+use futures::future::{err, join_all, Either};
 use futures::sink::Sink;
 use futures::sync::mpsc::{Receiver, Sender};
 use futures::sync::{mpsc, oneshot};
-use futures::{Future, Stream};
-use log::info;
+use futures::{stream, Future, Stream};
+use log::{error, info};
+
+use events::{EventBus, Rejection, StageEvent};
 
 use crate::vm::performance::Performance;
 use crate::vm::{Mailbox, VM};
 use crate::{
     crypto::{
         hashes::Identifiable,
-        sketches::{odd_sketch::OddSketch, SketchInsertable},
+        sketches::{
+            dummy_sketch::DummySketch, odd_sketch::OddSketch, Decodable, SketchInsertable,
+            Sketchable,
+        },
     },
     daemon::{Origin, Priority},
-    db::{mongodb::*, storing::Storable},
-    ego::ego::Ego,
+    db::{
+        blocking,
+        mongodb::*,
+        storing::{remove_performance, revert_state, Storable},
+    },
+    ego::{ego::Ego, peer_ego::PeerEgo},
+    net::bans::Misbehaviour,
     primitives::{
         act::{Act, Message},
+        status::{Expectation, PeerStatus, Status},
         transaction::*,
         tx_pool::TxPool,
         work::WorkState,
@@ -31,10 +45,175 @@ use crate::{
     utils::constants::{CONFIG, HASH_LEN},
 };
 
+#[derive(Debug, PartialEq)]
+pub enum Schedule {
+    Now,
+    Queue,
+    Preempt,
+}
+
+// Decide when a local batch is processed relative to reconciliation
+pub fn schedule_local(status: &Status, priority: Priority) -> Schedule {
+    match (status, priority) {
+        (Status::Idle, _) => Schedule::Now,
+        (Status::Pulling, Priority::Standard) => Schedule::Queue,
+        (Status::Pulling, Priority::Force) => Schedule::Preempt,
+    }
+}
+
+// Ids of submitted local transactions not yet stored, including those deferred in the mempool
+pub type InFlight = Arc<Mutex<HashSet<Bytes>>>;
+
+pub fn is_stored(db: &MongoDB, tx: &Transaction) -> bool {
+    match Transaction::from_db(&mut db.clone(), tx.get_id()) {
+        Ok(Some(_)) => true,
+        Ok(None) => false,
+        Err(err) => {
+            error!(target: "stage_event", "failed to query tx store: {}", err);
+            false
+        }
+    }
+}
+
+// Ids of the transactions already in the tx store
+fn stored_ids(db: &MongoDB, txs: &[Transaction]) -> HashSet<Bytes> {
+    txs.iter()
+        .filter(|tx| is_stored(db, tx))
+        .map(|tx| tx.get_id())
+        .collect()
+}
+
+// Drop repeated and already stored transactions, preserving order
+pub fn filter_duplicates<F>(txs: Vec<Transaction>, is_stored: F) -> Vec<Transaction>
+where
+    F: Fn(&Transaction) -> bool,
+{
+    let mut seen = HashSet::with_capacity(txs.len());
+    txs.into_iter()
+        .filter(|tx| seen.insert(tx.get_id()) && !is_stored(tx))
+        .collect()
+}
+
+// Depth of the channels between pipeline stages
+const PIPELINE_DEPTH: usize = 8;
+
+// Execute transactions in order, a failed performance leaves the rest of the batch intact
+fn perform(
+    db: MongoDB,
+    txs: Vec<Transaction>,
+) -> impl Future<Item = Vec<Option<Performance>>, Error = ()> {
+    let performances: Vec<_> = txs
+        .into_iter()
+        .map(|tx| {
+            Performance::from_tx(db.clone(), tx).then(|res| {
+                if res.is_err() {
+                    error!(target: "stage_event", "performance failed");
+                }
+                Ok(res.ok())
+            })
+        })
+        .collect();
+    join_all(performances)
+}
+
+// Split performed transactions from those that failed to run, which are removed from the tx store
+fn split_performed(
+    db: &MongoDB,
+    txs: Vec<Transaction>,
+    performances: Vec<Option<Performance>>,
+) -> (Vec<Transaction>, Vec<Performance>, Vec<Transaction>) {
+    let mut performed = (vec![], vec![]);
+    let mut failed = vec![];
+    for (tx, performance) in txs.into_iter().zip(performances) {
+        match performance {
+            Some(performance) => {
+                performed.0.push(tx);
+                performed.1.push(performance);
+            }
+            None => {
+                if let Err(err) = revert_state(db, &tx.get_id()) {
+                    error!(target: "stage_event", "failed to remove transaction: {}", err);
+                }
+                failed.push(tx);
+            }
+        }
+    }
+    (performed.0, performed.1, failed)
+}
+
+// Split transactions into those stored and those that failed to store
+fn store_txs(db: &MongoDB, txs: Vec<Transaction>) -> (Vec<Transaction>, Vec<Transaction>) {
+    txs.into_iter()
+        .partition(|tx| match tx.to_db(&mut db.clone(), None) {
+            Ok(_) => true,
+            Err(err) => {
+                error!(target: "stage_event", "failed to store transaction: {}", err);
+                false
+            }
+        })
+}
+
+// Persist performances and their events
+fn store_performances(db: &MongoDB, txs: &[Transaction], performances: &[Performance]) {
+    for (tx, performance) in txs.iter().zip(performances.iter()) {
+        if let Err(err) = performance.to_db(&mut db.clone(), Some(tx.get_id())) {
+            error!(target: "stage_event", "failed to store performance: {}", err);
+        }
+    }
+}
+
+// Work passed down the pipeline in order
+pub enum Job {
+    Execute(Vec<Transaction>),
+    Revert(HashSet<Bytes>), // Transactions excluded from an adopted peer state
+}
+
+enum Commit {
+    Performances(Vec<Transaction>, Vec<Option<Performance>>, Vec<Transaction>), // Stored, run, failed to store
+    Revert(Vec<Transaction>),
+}
+
+// Revert the state effects of stored transactions, returning them. Transactions performed
+// after them are not re-executed, state they derived from the reverted writes is kept
+pub fn revert_txs(db: &MongoDB, ids: &HashSet<Bytes>) -> Vec<Transaction> {
+    ids.iter()
+        .filter_map(
+            |id| match Transaction::from_db(&mut db.clone(), id.clone()) {
+                Ok(Some(tx)) => Some(tx),
+                Ok(None) => {
+                    error!(target: "stage_event", "reverted transaction {:?} not stored", id);
+                    None
+                }
+                Err(err) => {
+                    error!(target: "stage_event", "failed to query tx store: {}", err);
+                    None
+                }
+            },
+        )
+        .filter(|tx| match revert_state(db, &tx.get_id()) {
+            Ok(_) => true,
+            Err(err) => {
+                error!(target: "stage_event", "failed to revert transaction: {}", err);
+                false
+            }
+        })
+        .collect()
+}
+
+/*
+    Ingest    ->    Execute    ->    Commit
+      ^ Scheduling, validation and ego updates
+                      ^ Transactions stored, performances run
+                                       ^ Performances stored
+*/
+#[derive(Clone)]
 pub struct Stage {
     ego: Arc<Mutex<Ego>>,
     db: MongoDB,
     ego_bus: Arc<Mutex<Bus<(OddSketch, Bytes)>>>,
+    in_flight: InFlight,
+    pipeline: InFlight, // Accepted by ingest but not yet committed
+    events: EventBus,
 }
 
 impl Stage {
@@ -43,116 +222,453 @@ impl Stage {
             ego,
             db,
             ego_bus: Arc::new(Mutex::new(ego_bus)),
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            pipeline: Arc::new(Mutex::new(HashSet::new())),
+            events: EventBus::default(),
         }
     }
 
+    pub fn get_in_flight(&self) -> InFlight {
+        self.in_flight.clone()
+    }
+
+    // Handle for subscribing to stage events, subscribe before the manager consumes the stage
+    pub fn get_events(&self) -> EventBus {
+        self.events.clone()
+    }
+
     pub fn manager(
         self,
         mempool: Arc<Mutex<TxPool>>,
         incoming: futures::sync::mpsc::Receiver<(Origin, TxPool, Priority)>,
+    ) -> impl Future<Item = (), Error = ()> + Send {
+        let (execute_send, execute_recv) = mpsc::channel::<Job>(PIPELINE_DEPTH);
+        let (commit_send, commit_recv) = mpsc::channel::<Commit>(PIPELINE_DEPTH);
+
+        // Execute batches in order, the next batch waits for the previous performances
+        let db_inner = self.db.clone();
+        let execute = execute_recv.for_each(move |job| {
+            let db = db_inner.clone();
+            let commit_send = commit_send.clone();
+            let commit = match job {
+                Job::Execute(txs) => {
+                    info!(target: "stage_event", "executing tx batch of {}", txs.len());
+                    Either::A(
+                        blocking(move || {
+                            let (txs, failed) = store_txs(&db, txs);
+                            (db, txs, failed)
+                        })
+                        .and_then(|(db, txs, failed)| {
+                            perform(db, txs.clone())
+                                .map(|performances| Commit::Performances(txs, performances, failed))
+                        }),
+                    )
+                }
+                Job::Revert(ids) => {
+                    info!(target: "stage_event", "reverting {} transactions", ids.len());
+                    Either::B(blocking(move || Commit::Revert(revert_txs(&db, &ids))))
+                }
+            };
+            commit.and_then(move |commit| commit_send.send(commit).map(|_| ()).map_err(|_| ()))
+        });
+
+        // Commit performances, reverted transactions return to the mempool
+        let db_inner = self.db.clone();
+        let in_flight = self.in_flight.clone();
+        let pipeline = self.pipeline.clone();
+        let mempool_inner = mempool.clone();
+        let events_inner = self.events.clone();
+        let stage = self.clone();
+        let commit = commit_recv.for_each(move |commit| {
+            let db = db_inner.clone();
+            let in_flight = in_flight.clone();
+            let pipeline = pipeline.clone();
+            let mempool = mempool_inner.clone();
+            let events = events_inner.clone();
+            let stage = stage.clone();
+            blocking(move || match commit {
+                Commit::Performances(txs, performances, unstored) => {
+                    let (mut txs, performances, unperformed) =
+                        split_performed(&db, txs, performances);
+                    store_performances(&db, &txs, &performances);
+                    info!(target: "stage_event", "committed tx batch of {}", txs.len());
+                    for (tx, performance) in txs.iter().zip(performances.iter()) {
+                        events.publish_performance(tx.get_id(), performance);
+                    }
+                    stage.drop_failed(&unstored, Rejection::StoreFailed);
+                    stage.drop_failed(&unperformed, Rejection::RunFailed);
+                    txs.extend(unstored);
+                    txs.extend(unperformed);
+                    (txs, false)
+                }
+                Commit::Revert(txs) => {
+                    for tx in &txs {
+                        if let Err(err) = remove_performance(&db, &tx.get_id()) {
+                            error!(target: "stage_event", "failed to remove performance: {}", err);
+                        }
+                        events.publish(StageEvent::Reverted { tx_id: tx.get_id() });
+                    }
+                    (txs, true)
+                }
+            })
+            .map(move |(txs, reverted)| {
+                {
+                    let mut in_flight_guard = in_flight.lock().unwrap();
+                    let mut pipeline_guard = pipeline.lock().unwrap();
+                    for tx in &txs {
+                        let tx_id = tx.get_id();
+                        in_flight_guard.remove(&tx_id);
+                        pipeline_guard.remove(&tx_id);
+                    }
+                }
+                if reverted && !txs.is_empty() {
+                    info!(target: "stage_event", "returning {} transactions to mempool", txs.len());
+                    if let Err(err) = mempool.lock().unwrap().insert_batch(txs, false) {
+                        error!(target: "stage_event", "failed to return to mempool: {}", err);
+                    }
+                }
+            })
+        });
+
+        let ingest = self.ingest(mempool, incoming, execute_send);
+        ingest.join3(execute, commit).map(|_| ())
+    }
+
+    fn ingest(
+        self,
+        mempool: Arc<Mutex<TxPool>>,
+        incoming: futures::sync::mpsc::Receiver<(Origin, TxPool, Priority)>,
+        execute_send: Sender<Job>,
     ) -> impl Future<Item = (), Error = ()> + Send {
         incoming.for_each(move |(origin, txs, priority)| {
-            let performances = match origin {
+            let stage = self.clone();
+            let db = self.db.clone();
+            let mempool = mempool.clone();
+            let execute_send = execute_send.clone();
+            let txs = txs.into_sorted_txs();
+            let jobs = match origin {
                 Origin::Peer(peer_ego_arc) => {
-                    unreachable!()
-                    // self.process_txs_from_peer(peer_ego_arc.clone(), txs, priority)
-                    // Update ego
-                    // ego_guard.pull(
-                    //     peer_ego_guard.get_oddsketch(),
-                    //     peer_ego_guard.get_expected_minisketch(),
-                    //     peer_ego_guard.get_root(),
-                    // );
+                    let mut jobs = stage.process_txs_from_peer(peer_ego_arc, txs, priority);
+
+                    // Reconciliation complete, catch up on batches waiting in the mempool
+                    let mempool_inner = mempool.clone();
+                    Either::A(blocking(move || mempool::drain(&mempool_inner, &db)).map(
+                        move |mempool_txs| {
+                            if !mempool_txs.is_empty() {
+                                // Stored transactions were dropped while draining
+                                jobs.extend(
+                                    stage
+                                        .process_local_txs(
+                                            mempool_txs,
+                                            Priority::Standard,
+                                            &HashSet::new(),
+                                            &mempool,
+                                        )
+                                        .map(Job::Execute),
+                                );
+                            }
+                            jobs
+                        },
+                    ))
                 }
-                Origin::RPC => self.process_txs_from_rpc(txs.clone(), priority),
+                Origin::RPC | Origin::Mempool => Either::B(
+                    blocking(move || {
+                        let stored = stored_ids(&db, &txs);
+                        (txs, stored)
+                    })
+                    .map(move |(txs, stored)| {
+                        stage
+                            .process_local_txs(txs, priority, &stored, &mempool)
+                            .map(Job::Execute)
+                            .into_iter()
+                            .collect::<Vec<Job>>()
+                    }),
+                ),
             };
-            let done = futures::future::join_all(performances);
-            done.wait();
 
-            //Push to tx db and recreate ego
-            let mut ego_guard = self.ego.lock().unwrap();
-            let mut oddsketch = ego_guard.work_stack.get_oddsketch(); // TODO: Replace these with get &mut
-            let mut minisketch = ego_guard.get_minisketch();
+            // Backpressure from execution
+            jobs.and_then(move |jobs: Vec<Job>| {
+                execute_send
+                    .send_all(stream::iter_ok(jobs))
+                    .map(|_| ())
+                    .map_err(|_| ())
+            })
+        })
+    }
+
+    // Transactions accepted into the pipeline
+    fn accept(&self, txs: &[Transaction]) {
+        let mut pipeline_guard = self.pipeline.lock().unwrap();
+        for tx in txs {
+            let tx_id = tx.get_id();
+            pipeline_guard.insert(tx_id.clone());
+            self.events.publish(StageEvent::Accepted { tx_id });
+        }
+    }
 
-            for tx in txs.into_sorted_txs().iter() {
-                tx.to_db(&mut self.db.clone(), None);
-                oddsketch.insert(tx);
-                minisketch.insert(tx);
+    // Rejected transactions are no longer in flight, unless an earlier copy is in the pipeline
+    fn release(&self, ids: &[Bytes]) {
+        let pipeline_guard = self.pipeline.lock().unwrap();
+        let mut in_flight_guard = self.in_flight.lock().unwrap();
+        for id in ids {
+            if !pipeline_guard.contains(id) {
+                in_flight_guard.remove(id);
             }
-            let root = Bytes::from(&[0; HASH_LEN][..]); // TODO: Actually generate bytes
-            let mut ego_bus_guard = self.ego_bus.lock().unwrap();
-            ego_guard.work_stack.update_oddsketch(oddsketch.clone());
-            ego_guard.update_minisketch(minisketch);
-            ego_bus_guard.broadcast((oddsketch, root));
+        }
+    }
 
-            ok(())
-        })
+    fn reject<I>(&self, tx_ids: I, reason: Rejection)
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        for tx_id in tx_ids {
+            self.events.publish(StageEvent::Rejected {
+                tx_id,
+                reason: reason.clone(),
+            });
+        }
     }
 
-    pub fn process_txs_from_rpc(
+    // Remove transactions dropped from a batch from the ego and notify subscribers
+    fn drop_failed(&self, txs: &[Transaction], reason: Rejection) {
+        if txs.is_empty() {
+            return;
+        }
+        self.forget(txs);
+        self.reject(txs.iter().map(|tx| tx.get_id()), reason);
+    }
+
+    // Notify miners and subscribers of a new ego state
+    fn update_root(&self, oddsketch: OddSketch, root: Bytes) {
+        self.events.publish(StageEvent::RootUpdated {
+            oddsketch: oddsketch.clone(),
+            root: root.clone(),
+        });
+        self.ego_bus.lock().unwrap().broadcast((oddsketch, root));
+    }
+
+    // Remove transactions dropped from a batch from the ego, keeping it in line with the tx store
+    fn forget(&self, txs: &[Transaction]) {
+        let mut ego_guard = self.ego.lock().unwrap();
+        let minisketch = ego_guard.get_minisketch();
+
+        // An adopted peer state may no longer contain them
+        let ids: HashSet<Bytes> = txs
+            .iter()
+            .map(|tx| tx.get_id())
+            .filter(|id| minisketch.get_pos().contains(id))
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        info!(target: "stage_event", "removing {} unstored transactions from ego", ids.len());
+
+        let oddsketch = ego_guard
+            .work_stack
+            .get_oddsketch()
+            .xor(&OddSketch::sketch_ids(&ids));
+        let root = ego_guard.work_stack.get_root();
+        ego_guard.work_stack.update_oddsketch(oddsketch.clone());
+        ego_guard.update_minisketch(minisketch - DummySketch::from((ids, HashSet::new())));
+        drop(ego_guard);
+        self.update_root(oddsketch, root);
+    }
+
+    fn is_duplicate(&self, tx: &Transaction, stored: &HashSet<Bytes>) -> bool {
+        let tx_id = tx.get_id();
+        self.pipeline.lock().unwrap().contains(&tx_id) || stored.contains(&tx_id)
+    }
+
+    // Stored holds the ids of transactions already in the tx store
+    pub fn process_local_txs(
         &self,
-        txs: TxPool,
+        txs: Vec<Transaction>,
         priority: Priority,
-    ) -> Vec<impl Future<Item = Performance, Error = ()> + Send> {
-        info!(target: "stage_event", "processing tx batch from rpc");
-        txs.into_sorted_txs()
-            .iter()
-            .map(|tx| Performance::from_tx(self.db.clone(), tx.clone()))
-            .collect()
-    }
-
-    // pub fn process_txs_from_peer(
-    //     &self,
-    //     arc_peer_ego: Arc<Mutex<PeerEgo>>,
-    //     &txs: HashSet<Transaction>,
-    //     priority: Priority,
-    // ) -> Vec<Future<Item = Performance, Error = ()> + Send> {
-    //     // Lock ego and peer ego
-    //     let mut ego_guard = self.ego.lock().unwrap();
-    //     let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
-
-    //     // If received txs from reconciliation target check the payload matches reported
-    //     if peer_ego_guard.get_status() == PeerStatus::StatePull {
-    //         // Is reconcile target
-    //         // Cease reconciliation status
-    //         peer_ego_guard.update_status(PeerStatus::Gossiping);
-    //         if peer_ego_guard.is_expected_payload(&txs) {
-    //             // TODO: Send backstage and verify
-
-    //             if config.debugging.stage_verbose {
-    //                 println!("reconcile transactions sent to stage");
-    //             }
-
-    //             // TODO: Add to state function called here
-
-    //             // self.ego_bus.br
-    //             // Update ego
-    //             // ego_guard.pull(
-    //             //     peer_ego_guard.get_oddsketch(),
-    //             //     peer_ego_guard.get_expected_minisketch(),
-    //             //     peer_ego_guard.get_root(),
-    //             // );
-    //         }
-    //     } else {
-    //         if config.debugging.stage_verbose {
-    //             println!("non-reconcile transactions sent to stage");
-    //         }
-    //     }
-
-    //     // Send updated state immediately
-    //     peer_ego_guard.update_status(PeerStatus::Gossiping);
-    //     peer_ego_guard.update_work_status(WorkPeerStatus::Waiting);
-    //     peer_ego_guard.commit_work(&ego_guard);
-
-    //     tokio::spawn(
-    //         peer_ego_guard
-    //             .get_sink()
-    //             .send(net::messages::Message::Work {
-    //                 oddsketch: peer_ego_guard.get_oddsketch(),
-    //                 root: peer_ego_guard.get_root(),
-    //                 nonce: 0,
-    //             })
-    //             .map_err(|_| ())
-    //             .and_then(|_| ok(())),
-    //     );
-    // }
+        stored: &HashSet<Bytes>,
+        mempool: &Arc<Mutex<TxPool>>,
+    ) -> Option<Vec<Transaction>> {
+        let status = self.ego.lock().unwrap().get_status();
+        match schedule_local(&status, priority) {
+            Schedule::Now => (),
+            Schedule::Queue => {
+                // Drained again once reconciliation completes
+                info!(target: "stage_event", "returning local tx batch to mempool");
+                let ids: Vec<Bytes> = txs.iter().map(|tx| tx.get_id()).collect();
+                if let Err(err) = mempool.lock().unwrap().insert_batch(txs, false) {
+                    error!(target: "stage_event", "failed to return to mempool: {}", err);
+                    self.reject(ids.clone(), Rejection::MempoolFull);
+                    self.release(&ids);
+                }
+                return None;
+            }
+            Schedule::Preempt => {
+                // Abandon reconciliation, its payload will be rejected on arrival
+                info!(target: "stage_event", "local tx batch preempting reconciliation");
+                self.ego.lock().unwrap().update_status(Status::Idle);
+            }
+        }
+
+        // Replayed transactions would be executed twice and cancel out of the sketches
+        let n_txs = txs.len();
+        let ids: Vec<Bytes> = txs.iter().map(|tx| tx.get_id()).collect();
+        let txs = filter_duplicates(txs, |tx| self.is_duplicate(tx, stored));
+        if txs.len() != n_txs {
+            info!(
+                target: "stage_event",
+                "rejected {} duplicate transactions",
+                n_txs - txs.len()
+            );
+            let accepted: HashSet<Bytes> = txs.iter().map(|tx| tx.get_id()).collect();
+            let duplicates: HashSet<Bytes> = ids
+                .iter()
+                .filter(|id| !accepted.contains(*id))
+                .cloned()
+                .collect();
+            self.reject(duplicates, Rejection::Duplicate);
+        }
+        self.accept(&txs);
+        self.release(&ids);
+        if txs.is_empty() {
+            return None;
+        }
+
+        info!(target: "stage_event", "processing local tx batch");
+
+        // Recreate ego
+        let mut ego_guard = self.ego.lock().unwrap();
+        let mut oddsketch = ego_guard.work_stack.get_oddsketch(); // TODO: Replace these with get &mut
+        let mut minisketch = ego_guard.get_minisketch();
+        for tx in txs.iter() {
+            oddsketch.insert(tx);
+            minisketch.insert(tx);
+        }
+        let root = Bytes::from(&[0; HASH_LEN][..]); // TODO: Actually generate bytes
+        ego_guard.work_stack.update_oddsketch(oddsketch.clone());
+        ego_guard.update_minisketch(minisketch);
+        self.update_root(oddsketch, root);
+        Some(txs)
+    }
+
+    pub fn process_txs_from_peer(
+        &self,
+        arc_peer_ego: Arc<Mutex<PeerEgo>>,
+        txs: Vec<Transaction>,
+        priority: Priority,
+    ) -> Vec<Job> {
+        info!(target: "stage_event", "processing tx batch from peer");
+
+        // Only accept transactions from a reconciliation target
+        let expectation = match arc_peer_ego.lock().unwrap().get_status() {
+            PeerStatus::StatePull(expectation) => expectation,
+            _ => {
+                error!(target: "stage_event", "received transactions from non-reconcile target");
+                return vec![];
+            }
+        };
+
+        // Reconciliation may have been preempted
+        if self.ego.lock().unwrap().get_status() != Status::Pulling {
+            info!(target: "stage_event", "dropping transactions from preempted reconciliation");
+            self.reject(txs.iter().map(|tx| tx.get_id()), Rejection::Preempted);
+            arc_peer_ego.lock().unwrap().update_status(PeerStatus::Idle);
+            return vec![];
+        }
+
+        // Validate against expectation
+        let (oddsketch, minisketch) = {
+            let ego_guard = self.ego.lock().unwrap();
+            (
+                ego_guard.work_stack.get_oddsketch(),
+                ego_guard.get_minisketch(),
+            )
+        };
+        let (expected_minisketch, excess) =
+            match validate_peer_txs(&expectation, &txs, oddsketch, minisketch) {
+                Some(some) => some,
+                None => {
+                    error!(target: "stage_event", "reconcile transactions failed validation");
+                    self.reject(txs.iter().map(|tx| tx.get_id()), Rejection::InvalidPayload);
+                    let mut ego_guard = self.ego.lock().unwrap();
+                    let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+                    peer_ego_guard.penalise(Misbehaviour::InvalidReconcilePayload);
+                    peer_ego_guard.update_status(PeerStatus::Idle);
+                    ego_guard.update_status(Status::Idle);
+                    return vec![];
+                }
+            };
+        info!(target: "stage_event", "reconcile transactions passed validation");
+
+        // Adopting peer state rolls back our excess provisional transactions
+        if !accepts_peer_state(priority, &excess) {
+            info!(
+                target: "stage_event",
+                "rejecting reconciliation conflicting with {} provisional transactions",
+                excess.len()
+            );
+            self.reject(txs.iter().map(|tx| tx.get_id()), Rejection::Conflict);
+            let mut ego_guard = self.ego.lock().unwrap();
+            arc_peer_ego.lock().unwrap().update_status(PeerStatus::Idle);
+            ego_guard.update_status(Status::Idle);
+            return vec![];
+        }
+        let mut jobs = vec![];
+        if !excess.is_empty() {
+            info!(target: "stage_event", "rolling back {} provisional transactions", excess.len());
+            jobs.push(Job::Revert(excess));
+        }
+        self.accept(&txs);
+
+        // Adopt peer state
+        let mut ego_guard = self.ego.lock().unwrap();
+        let mut peer_ego_guard = arc_peer_ego.lock().unwrap();
+        let oddsketch = expectation.get_oddsketch();
+        let root = expectation.get_root();
+        ego_guard.pull(oddsketch.clone(), expected_minisketch, root.clone());
+        self.update_root(oddsketch, root);
+
+        // Send updated state immediately
+        peer_ego_guard.update_status(PeerStatus::Idle);
+        peer_ego_guard.push_work(ego_guard.get_work_stack(), ego_guard.get_minisketch());
+        ego_guard.update_status(Status::Idle);
+        jobs.push(Job::Execute(txs));
+        jobs
+    }
+}
+
+// Check a reconciliation payload brings us to the state the peer reported, returning
+// the expected minisketch and the ids of our transactions it excludes
+pub fn validate_peer_txs(
+    expectation: &Expectation,
+    txs: &[Transaction],
+    mut oddsketch: OddSketch,
+    mut minisketch: DummySketch,
+) -> Option<(DummySketch, HashSet<Bytes>)> {
+    let expected_minisketch = expectation.get_minisketch()?;
+
+    // Check payload
+    let tx_set: HashSet<Transaction> = txs.iter().cloned().collect();
+    if !expectation.is_expected_payload(&tx_set) {
+        return None;
+    }
+
+    // Check no expected transactions are still missing
+    for tx in txs {
+        oddsketch.insert(tx);
+        minisketch.insert(tx);
+    }
+    let (missing, excess) = (expected_minisketch.clone() - minisketch).decode().ok()?;
+    if !missing.is_empty() {
+        return None;
+    }
+
+    // Check oddsketch once excess transactions are removed
+    if oddsketch.xor(&OddSketch::sketch_ids(&excess)) != expectation.get_oddsketch() {
+        return None;
+    }
+    Some((expected_minisketch, excess))
+}
+
+// Only forced payloads may roll back provisional transactions
+pub fn accepts_peer_state(priority: Priority, excess: &HashSet<Bytes>) -> bool {
+    priority == Priority::Force || excess.is_empty()
 }
@@ -4,9 +4,11 @@ mod db_tests {
     use bytes::Bytes;
 
     use crate::{
-        crypto::hashes::*,
+        crypto::{hashes::*, signatures::ecdsa::generate_keypair},
         db::{mongodb::MongoDB, storing::*, DataType, Database},
-        primitives::transaction::*,
+        net::addresses::AddressRecord,
+        primitives::{act::Event, transaction::*},
+        vm::performance::Performance,
     };
 
     use bson::{bson, doc};
@@ -61,6 +63,144 @@ mod db_tests {
         assert!(tx_retrieved.unwrap().is_none());
     }
 
+    #[test]
+    fn test_put_get_performance_events() {
+        let mut db = MongoDB::open_db("tests_db_e").unwrap();
+        db.dropall(&DataType::Performance);
+        db.dropall(&DataType::Event);
+
+        let actor_id = Bytes::from(&[1; 32][..]);
+        let topic_a = Bytes::from(&b"topic_a"[..]);
+        let topic_b = Bytes::from(&b"topic_b"[..]);
+        let mut performance = Performance::default();
+        performance.add_event(
+            &actor_id,
+            Event::new(topic_a.clone(), Bytes::from(&b"1"[..])),
+        );
+        performance.add_event(
+            &actor_id,
+            Event::new(topic_b.clone(), Bytes::from(&b"2"[..])),
+        );
+        performance.add_event(
+            &actor_id,
+            Event::new(topic_a.clone(), Bytes::from(&b"3"[..])),
+        );
+        let perf_id = performance.get_id();
+        performance.to_db(&mut db, None).unwrap();
+
+        let performance_retrieved = Performance::from_db(&mut db, perf_id).unwrap().unwrap();
+        assert_eq!(performance, performance_retrieved);
+
+        let events = fetch_events(&db, actor_id.clone(), None, 0, 10).unwrap();
+        assert_eq!(events.len(), 3);
+        let events = fetch_events(&db, actor_id, Some(topic_a), 0, 10).unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.get_data())
+                .collect::<Vec<Bytes>>(),
+            vec![Bytes::from(&b"1"[..]), Bytes::from(&b"3"[..])]
+        );
+    }
+
+    #[test]
+    fn test_store_performance_twice() {
+        let mut db = MongoDB::open_db("tests_db_h").unwrap();
+        db.dropall(&DataType::Performance);
+        db.dropall(&DataType::Event);
+
+        let actor_id = Bytes::from(&[1; 32][..]);
+        let topic = Bytes::from(&b"topic"[..]);
+        let mut performance = Performance::default();
+        for i in 0..5u8 {
+            performance.add_event(&actor_id, Event::new(topic.clone(), Bytes::from(vec![i])));
+        }
+        performance.to_db(&mut db, None).unwrap();
+        performance.to_db(&mut db, None).unwrap();
+
+        let events = fetch_events(&db, actor_id.clone(), None, 0, 10).unwrap();
+        assert_eq!(events.len(), 5);
+
+        // Pages follow the order events were emitted in
+        let events = fetch_events(&db, actor_id, Some(topic), 1, 3).unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.get_data())
+                .collect::<Vec<Bytes>>(),
+            vec![
+                Bytes::from(vec![1]),
+                Bytes::from(vec![2]),
+                Bytes::from(vec![3])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_revert_performance() {
+        let mut db = MongoDB::open_db("tests_db_f").unwrap();
+        db.dropall(&DataType::TX);
+        db.dropall(&DataType::State);
+        db.dropall(&DataType::Performance);
+        db.dropall(&DataType::Event);
+
+        let tx = Transaction::new(0, Bytes::from(&b"aux"[..]), Bytes::from(&b"binary"[..]));
+        let tx_id = tx.get_id();
+        tx.to_db(&mut db, None).unwrap();
+
+        let actor_id = Bytes::from(&[1; 32][..]);
+        let mut performance = Performance::default();
+        performance.add_event(
+            &actor_id,
+            Event::new(Bytes::from(&b"topic"[..]), Bytes::new()),
+        );
+        performance.to_db(&mut db, Some(tx_id.clone())).unwrap();
+        db.put(
+            &DataType::State,
+            doc! {
+                "t" : Bson::Binary(BinarySubtype::Generic, actor_id.to_vec()),
+                "o" : Bson::Binary(BinarySubtype::Generic, tx_id.to_vec()),
+                "k" : Bson::Binary(BinarySubtype::Generic, b"key".to_vec()),
+                "v" : Bson::Binary(BinarySubtype::Generic, b"value".to_vec()),
+            },
+        )
+        .unwrap();
+
+        revert_state(&db, &tx_id).unwrap();
+        remove_performance(&db, &tx_id).unwrap();
+
+        assert!(Transaction::from_db(&mut db, tx_id.clone())
+            .unwrap()
+            .is_none());
+        assert!(Performance::from_db(&mut db, tx_id).unwrap().is_none());
+        assert!(fetch_events(&db, actor_id, None, 0, 10).unwrap().is_empty());
+        assert!(db.get(&DataType::State, doc! {}).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_store_load_addresses() {
+        let db = MongoDB::open_db("tests_db_g").unwrap();
+        db.dropall(&DataType::Address);
+
+        let (_, pubkey) = generate_keypair();
+        let mut record = AddressRecord::new("127.0.0.1:8332".parse().unwrap(), 1000);
+        record.pubkey = Some(pubkey);
+        record.banned_until = Some(2000);
+        record.n_successes = 3;
+        let other_record = AddressRecord::new("127.0.0.2:8332".parse().unwrap(), 1000);
+
+        store_address(&db, &record).unwrap();
+        store_address(&db, &other_record).unwrap();
+
+        // Storing again replaces the whole record
+        record.n_failures = 1;
+        record.banned_until = None;
+        store_address(&db, &record).unwrap();
+        remove_address(&db, &other_record.addr).unwrap();
+
+        assert_eq!(load_addresses(&db).unwrap(), vec![record]);
+    }
+
     #[test]
     fn test_ordering() {
         let db = MongoDB::open_db("tests_db_d").unwrap();
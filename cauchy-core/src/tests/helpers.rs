@@ -0,0 +1,11 @@
+use bytes::Bytes;
+use rand::Rng;
+
+use crate::primitives::transaction::Transaction;
+
+pub fn generate_random_tx(time: u64) -> Transaction {
+    let mut rng = rand::thread_rng();
+    let aux_data: [u8; 8] = rng.gen();
+    let binary: [u8; 8] = rng.gen();
+    Transaction::new(time, Bytes::from(&aux_data[..]), Bytes::from(&binary[..]))
+}
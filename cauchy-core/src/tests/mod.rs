@@ -1,5 +1,8 @@
 mod crypto;
 mod db_tests;
+mod helpers;
+mod net;
 mod primitives;
+mod stage_tests;
 mod utils;
 mod vm;
@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+use crate::{
+    crypto::signatures::ecdsa::generate_keypair,
+    net::{
+        addresses::{address_group, is_valid_gossip, normalise, AddressManager, AddressRecord},
+        peers::{Peer, Peers},
+    },
+    utils::timing::get_current_time,
+};
+
+const BACKOFF: (u64, u64) = (1_000, 60_000);
+
+fn generate_addr(i: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8000 + i))
+}
+
+#[test]
+fn test_insert_batch() {
+    let mut addresses = AddressManager::with_capacity(8);
+    let peers = Peers::new((0..4).map(|i| Peer::new(generate_addr(i))).collect());
+    addresses.insert_batch(peers, &generate_addr(8));
+
+    assert_eq!(addresses.len(), 4);
+    assert!(addresses.contains(&generate_addr(0)));
+}
+
+#[test]
+fn test_invalid_gossip() {
+    let public = SocketAddr::from(([8, 8, 8, 8], 8332));
+    assert!(is_valid_gossip(&public, &public));
+    for invalid in &["8.8.8.8:0", "0.0.0.0:8332", "224.0.0.1:8332", "[::]:8332"] {
+        assert!(!is_valid_gossip(&invalid.parse().unwrap(), &public));
+    }
+
+    // Loopback only makes sense from a local peer
+    assert!(!is_valid_gossip(&generate_addr(0), &public));
+    assert!(is_valid_gossip(&generate_addr(0), &generate_addr(1)));
+
+    let mut addresses = AddressManager::with_capacity(8);
+    let peers = Peers::new(vec![Peer::new(generate_addr(0)), Peer::new(public)]);
+    addresses.insert_batch(peers, &public);
+    assert_eq!(addresses.len(), 1);
+    assert!(addresses.contains(&public));
+}
+
+#[test]
+fn test_gossip_last_seen() {
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.insert_record(AddressRecord::new(generate_addr(0), 0));
+
+    // Gossip does not refresh known addresses
+    let peers = Peers::new(vec![Peer::new(generate_addr(0))]);
+    addresses.insert_batch(peers, &generate_addr(1));
+    assert_eq!(addresses.get(&generate_addr(0)).unwrap().last_seen, 0);
+    assert!(addresses.take_dirty().0.is_empty());
+}
+
+#[test]
+fn test_capacity() {
+    let mut addresses = AddressManager::with_capacity(4);
+    for i in 0..8 {
+        addresses.insert(generate_addr(i));
+    }
+
+    assert_eq!(addresses.len(), 4);
+    assert_eq!(addresses.sample_n(8).into_vec().len(), 4);
+}
+
+#[test]
+fn test_dial_candidates() {
+    let mut addresses = AddressManager::with_capacity(8);
+    for i in 0..4 {
+        addresses.insert(generate_addr(i));
+    }
+
+    let exclude: HashSet<SocketAddr> = (0..2).map(generate_addr).collect();
+    let candidates = addresses.dial_candidates(8, &exclude, &HashSet::new(), BACKOFF);
+
+    assert_eq!(candidates.len(), 2);
+    assert!(candidates.iter().all(|addr| !exclude.contains(addr)));
+}
+
+#[test]
+fn test_record_failure() {
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.insert(generate_addr(0));
+
+    addresses.record_failure(generate_addr(0), 2);
+    assert_eq!(addresses.get(&generate_addr(0)).unwrap().n_failures, 1);
+
+    addresses.record_failure(generate_addr(0), 2);
+    assert!(!addresses.contains(&generate_addr(0)));
+}
+
+#[test]
+fn test_good_candidates_first() {
+    let (_, pubkey) = generate_keypair();
+    let mut addresses = AddressManager::with_capacity(8);
+    for i in 0..4 {
+        addresses.insert(generate_addr(i));
+    }
+    addresses.record_success(generate_addr(3), pubkey);
+
+    let candidates = addresses.dial_candidates(1, &HashSet::new(), &HashSet::new(), BACKOFF);
+    assert_eq!(candidates, vec![generate_addr(3)]);
+}
+
+#[test]
+fn test_banned_candidates() {
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.insert(generate_addr(0));
+    addresses.record_ban(generate_addr(0), None, get_current_time() + 60_000);
+
+    assert!(addresses
+        .dial_candidates(8, &HashSet::new(), &HashSet::new(), BACKOFF)
+        .is_empty());
+    assert!(addresses.sample_n(8).into_vec().is_empty());
+}
+
+#[test]
+fn test_ban_unknown() {
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.record_ban(generate_addr(0), None, get_current_time() + 60_000);
+    assert!(!addresses.contains(&generate_addr(0)));
+    assert!(addresses.take_dirty().0.is_empty());
+}
+
+#[test]
+fn test_take_dirty() {
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.insert(generate_addr(0));
+    addresses.insert(generate_addr(1));
+    addresses.remove(&generate_addr(1));
+
+    let (updated, removed) = addresses.take_dirty();
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].addr, generate_addr(0));
+    assert_eq!(removed, vec![generate_addr(1)]);
+
+    // Loaded records are not dirty
+    addresses.insert_record(AddressRecord::new(generate_addr(2), 0));
+    let (updated, removed) = addresses.take_dirty();
+    assert!(updated.is_empty() && removed.is_empty());
+}
+
+#[test]
+fn test_backoff() {
+    let mut record = AddressRecord::new(generate_addr(0), 0);
+    record.last_attempt = 10_000;
+    assert_eq!(record.next_attempt(1_000, 60_000), 10_000);
+
+    record.n_failures = 3;
+    assert_eq!(record.next_attempt(1_000, 60_000), 14_000);
+
+    record.n_failures = 100;
+    assert_eq!(record.next_attempt(1_000, 60_000), 70_000);
+}
+
+#[test]
+fn test_backoff_candidates() {
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.insert(generate_addr(0));
+    addresses.record_attempt(generate_addr(0), get_current_time());
+    addresses.record_failure(generate_addr(0), 10);
+
+    assert!(addresses
+        .dial_candidates(8, &HashSet::new(), &HashSet::new(), BACKOFF)
+        .is_empty());
+}
+
+#[test]
+fn test_address_groups() {
+    let a = SocketAddr::from(([8, 8, 4, 4], 8332));
+    let b = SocketAddr::from(([8, 8, 8, 8], 8332));
+    let c = SocketAddr::from(([9, 9, 9, 9], 8332));
+    assert_eq!(address_group(&a), address_group(&b));
+    assert_ne!(address_group(&a), address_group(&c));
+    assert_eq!(address_group(&generate_addr(0)), None);
+
+    // One outbound candidate per group
+    let mut addresses = AddressManager::with_capacity(8);
+    addresses.insert(a);
+    addresses.insert(b);
+    addresses.insert(c);
+    let candidates = addresses.dial_candidates(8, &HashSet::new(), &HashSet::new(), BACKOFF);
+    assert_eq!(candidates.len(), 2);
+
+    let groups = vec![address_group(&c).unwrap()].into_iter().collect();
+    let candidates = addresses.dial_candidates(8, &HashSet::new(), &groups, BACKOFF);
+    assert_eq!(candidates.len(), 1);
+    assert_ne!(candidates[0], c);
+}
+
+#[test]
+fn test_normalise() {
+    let mapped: SocketAddr = "[::ffff:8.8.8.8]:8332".parse().unwrap();
+    let v4 = SocketAddr::from(([8, 8, 8, 8], 8332));
+    assert_eq!(normalise(mapped), v4);
+    assert_eq!(address_group(&mapped), address_group(&v4));
+
+    // Only mapped addresses are converted
+    let loopback: SocketAddr = "[::1]:8332".parse().unwrap();
+    assert_eq!(normalise(loopback), loopback);
+}
+
+#[test]
+fn test_local_v6_groups() {
+    for local in &["[fc00::1]:8332", "[fd12:3456::1]:8332", "[fe80::1]:8332"] {
+        assert_eq!(address_group(&local.parse().unwrap()), None);
+    }
+    assert!(address_group(&"[2001:4860::1]:8332".parse().unwrap()).is_some());
+}
@@ -0,0 +1,120 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    crypto::signatures::ecdsa::generate_keypair,
+    ego::{ego::Ego, peer_ego::PeerEgo},
+    net::bans::{BanList, Misbehaviour, ScoreBook},
+    primitives::arena::Arena,
+    utils::constants::CONFIG,
+};
+
+#[test]
+fn test_ban_addr() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+    let other_port: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+    let other_addr: SocketAddr = "127.0.0.2:8332".parse().unwrap();
+
+    let mut bans = BanList::default();
+    bans.ban(&addr, None, Duration::from_secs(60));
+
+    // Bans apply to the whole address
+    assert!(bans.is_banned(&addr));
+    assert!(bans.is_banned(&other_port));
+    assert!(!bans.is_banned(&other_addr));
+}
+
+#[test]
+fn test_ban_pubkey() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+    let (_, pubkey) = generate_keypair();
+    let (_, other_pubkey) = generate_keypair();
+
+    let mut bans = BanList::default();
+    bans.ban(&addr, Some(pubkey), Duration::from_secs(60));
+
+    assert!(bans.is_banned_pubkey(&pubkey));
+    assert!(!bans.is_banned_pubkey(&other_pubkey));
+}
+
+#[test]
+fn test_ban_expiry() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+    let (_, pubkey) = generate_keypair();
+
+    let mut bans = BanList::default();
+    bans.ban(&addr, Some(pubkey), Duration::from_millis(10));
+    sleep(Duration::from_millis(20));
+
+    assert!(!bans.is_banned(&addr));
+    assert!(!bans.is_banned_pubkey(&pubkey));
+}
+
+#[test]
+fn test_penalise() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+
+    peer_ego.penalise(Misbehaviour::UnsolicitedWork);
+    assert_eq!(
+        peer_ego.get_score(),
+        Misbehaviour::UnsolicitedWork.penalty()
+    );
+    assert!(!peer_ego.is_banned());
+
+    peer_ego.penalise(Misbehaviour::FraudulentMiniSketch);
+    assert!(peer_ego.is_banned());
+}
+
+#[test]
+fn test_repeated_malformed() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+    let (sk, pk) = generate_keypair();
+    let mut arena = Arena::new(Arc::new(Mutex::new(Ego::new(pk, sk))));
+
+    // Each malformed message ends its connection, the score outlives it
+    let mut n_connections = 0;
+    while !arena.is_banned(&addr) {
+        let (peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+        let arc_peer_ego = Arc::new(Mutex::new(peer_ego));
+        arena.new_peer(&addr, arc_peer_ego.clone(), false);
+        arc_peer_ego
+            .lock()
+            .unwrap()
+            .penalise(Misbehaviour::MalformedMessage);
+        arena.remove_peer(&addr);
+        n_connections += 1;
+    }
+    assert!(n_connections > 1);
+    assert!(n_connections * Misbehaviour::MalformedMessage.penalty() >= CONFIG.banning.threshold);
+}
+
+#[test]
+fn test_score_carry_over() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+    let other_addr: SocketAddr = "127.0.0.2:8332".parse().unwrap();
+    let (_, pubkey) = generate_keypair();
+
+    let mut scores = ScoreBook::new(Duration::from_secs(60));
+    scores.record(&addr, Some(pubkey), 30);
+    assert_eq!(scores.score(&addr, None), 30);
+    assert_eq!(scores.score(&other_addr, Some(&pubkey)), 30);
+    assert_eq!(scores.score(&other_addr, None), 0);
+
+    // Lower scores do not overwrite higher ones
+    scores.record(&addr, None, 10);
+    assert_eq!(scores.score(&addr, None), 30);
+}
+
+#[test]
+fn test_score_decay() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+
+    let mut scores = ScoreBook::new(Duration::from_millis(1));
+    scores.record(&addr, None, 10);
+    sleep(Duration::from_millis(20));
+    assert_eq!(scores.score(&addr, None), 0);
+}
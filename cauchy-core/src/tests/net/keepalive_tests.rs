@@ -0,0 +1,76 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{future, Stream};
+use tokio::runtime::current_thread;
+
+use crate::{ego::peer_ego::PeerEgo, net::messages::Message, primitives::arena::sample_by_rtt};
+
+#[test]
+fn test_ping_pong() {
+    let (mut peer_ego, peer_stream) = PeerEgo::new(Bytes::new());
+    peer_ego.set_version(2, 0);
+    current_thread::block_on_all(future::lazy(|| {
+        peer_ego.ping();
+        future::ok::<(), ()>(())
+    }))
+    .unwrap();
+
+    let nonce = match peer_stream.wait().next() {
+        Some(Ok(Message::Ping { nonce })) => nonce,
+        _ => panic!("expected ping"),
+    };
+
+    // Outstanding pings expire
+    sleep(Duration::from_millis(1));
+    assert!(peer_ego.is_unresponsive(Duration::from_millis(0)));
+    assert!(!peer_ego.is_unresponsive(Duration::from_secs(60)));
+
+    // Only the matching nonce answers the ping
+    assert!(!peer_ego.pong(nonce.wrapping_add(1)));
+    assert!(peer_ego.get_rtt().is_none());
+    assert!(peer_ego.pong(nonce));
+    assert!(peer_ego.get_rtt().is_some());
+    assert!(!peer_ego.pong(nonce));
+    assert!(!peer_ego.is_unresponsive(Duration::from_millis(0)));
+}
+
+#[test]
+fn test_versionless_unresponsive() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    sleep(Duration::from_millis(1));
+    assert!(peer_ego.is_unresponsive(Duration::from_millis(0)));
+    assert!(!peer_ego.is_unresponsive(Duration::from_secs(60)));
+
+    peer_ego.set_version(2, 0);
+    assert!(!peer_ego.is_unresponsive(Duration::from_millis(0)));
+}
+
+#[test]
+fn test_silent() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    sleep(Duration::from_millis(2));
+    assert!(peer_ego.is_silent(Duration::from_millis(1)));
+
+    peer_ego.mark_seen();
+    assert!(!peer_ego.is_silent(Duration::from_secs(60)));
+}
+
+#[test]
+fn test_sample_by_rtt() {
+    let candidates = || {
+        vec![
+            (Duration::from_millis(1), "fast"),
+            (Duration::from_millis(100), "slow"),
+        ]
+    };
+    assert_eq!(sample_by_rtt(candidates(), 3).len(), 2);
+
+    // Slow peers are picked less often, but still picked
+    let n_slow = (0..1000)
+        .filter(|_| sample_by_rtt(candidates(), 1) == vec!["slow"])
+        .count();
+    assert!(n_slow > 0);
+    assert!(n_slow < 500);
+}
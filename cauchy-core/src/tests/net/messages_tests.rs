@@ -0,0 +1,200 @@
+use std::net::SocketAddr;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::codec::{Decoder, Encoder};
+
+use crate::{
+    crypto::sketches::odd_sketch::OddSketch,
+    net::{
+        messages::{max_message_len, Message, MessageCodec},
+        peers::{Peer, Peers},
+    },
+    primitives::{
+        transaction::Transaction,
+        varint::VarInt,
+        work::{WorkStack, WorkState},
+    },
+    utils::constants::{
+        HASH_LEN, MAX_FRAME_LEN, MAX_IDS_PER_MSG, MAX_TXS_PAYLOAD_LEN, SKETCH_CAPACITY,
+    },
+};
+
+#[test]
+fn test_peers_round_trip() {
+    let mut codec = MessageCodec::default();
+    let mut addrs: Vec<SocketAddr> = (0..4)
+        .map(|i| SocketAddr::from(([10, 0, 0, i], 8332)))
+        .collect();
+    addrs.push(SocketAddr::from(([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1], 8332)));
+    let peers = Peers::new(addrs.iter().cloned().map(Peer::new).collect());
+
+    let mut buf = BytesMut::new();
+    codec.encode(Message::Peers { peers }, &mut buf).unwrap();
+    codec.encode(Message::GetPeers, &mut buf).unwrap();
+
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::Peers { peers }) => {
+            let decoded: Vec<SocketAddr> = peers.into_vec().iter().map(Peer::get_addr).collect();
+            assert_eq!(decoded, addrs)
+        }
+        _ => panic!("expected peers"),
+    }
+
+    // The following message is left intact
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::GetPeers) => (),
+        _ => panic!("expected get peers"),
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_version_round_trip() {
+    let mut codec = MessageCodec::default();
+    let mut buf = BytesMut::new();
+    codec
+        .encode(
+            Message::Version {
+                version: 3,
+                features: 0b101,
+            },
+            &mut buf,
+        )
+        .unwrap();
+
+    // Incomplete versions wait for more bytes
+    let mut partial = BytesMut::from(&buf[..2]);
+    assert!(codec.decode(&mut partial).unwrap().is_none());
+
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::Version { version, features }) => {
+            assert_eq!(version, 3);
+            assert_eq!(features, 0b101);
+        }
+        _ => panic!("expected version"),
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_transactions_confined() {
+    let mut codec = MessageCodec::default();
+    let txs = vec![
+        Transaction::new(1, Bytes::from(&b"aux"[..]), Bytes::from(&b"binary"[..])),
+        Transaction::new(2, Bytes::new(), Bytes::from(&b"other binary"[..])),
+    ];
+
+    let mut buf = BytesMut::new();
+    codec
+        .encode(Message::Transactions { txs: txs.clone() }, &mut buf)
+        .unwrap();
+    codec.encode(Message::GetPeers, &mut buf).unwrap();
+
+    // Decoding stops at the declared payload
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::Transactions { txs: decoded }) => assert_eq!(decoded, txs),
+        _ => panic!("expected transactions"),
+    }
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::GetPeers) => (),
+        _ => panic!("expected get peers"),
+    }
+}
+
+#[test]
+fn test_truncated_transaction() {
+    let mut codec = MessageCodec::default();
+    let raw = Bytes::from(Transaction::new(
+        1,
+        Bytes::new(),
+        Bytes::from(&b"binary"[..]),
+    ));
+    let truncated = raw.slice_to(raw.len() - 1);
+
+    let mut buf = BytesMut::new();
+    buf.reserve(1);
+    buf.put_u8(5);
+    buf.extend(Bytes::from(VarInt::new(truncated.len() as u64)));
+    buf.extend(truncated);
+    codec.encode(Message::GetPeers, &mut buf).unwrap();
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_too_many_ids() {
+    let mut codec = MessageCodec::default();
+    let mut buf = BytesMut::new();
+    buf.reserve(1);
+    buf.put_u8(4);
+    buf.extend(Bytes::from(VarInt::new(MAX_IDS_PER_MSG as u64 + 1)));
+    buf.extend(Bytes::from(vec![0; HASH_LEN]));
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_work_round_trip() {
+    let mut codec = MessageCodec::default();
+    let root = Bytes::from(vec![7; HASH_LEN]);
+    let work_stack = WorkStack::new(root.clone(), OddSketch::default(), 42);
+
+    let mut buf = BytesMut::new();
+    codec.encode(Message::Work(work_stack), &mut buf).unwrap();
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::Work(decoded)) => {
+            assert_eq!(decoded.get_root(), root);
+            assert_eq!(decoded.get_oddsketch(), OddSketch::default());
+            assert_eq!(decoded.get_nonce(), 42);
+        }
+        _ => panic!("expected work"),
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_work_sketch_size() {
+    let mut codec = MessageCodec::default();
+    let mut buf = BytesMut::new();
+    buf.reserve(1);
+    buf.put_u8(2);
+    buf.extend(Bytes::from(VarInt::new(SKETCH_CAPACITY as u64 * 2)));
+    buf.extend(Bytes::from(vec![0; 2 * SKETCH_CAPACITY + HASH_LEN + 1]));
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_legacy_work() {
+    let mut legacy_codec = MessageCodec::with_version(1);
+    let root = Bytes::from(vec![7; HASH_LEN]);
+    let work_stack = WorkStack::new(root.clone(), OddSketch::default(), 42);
+
+    // Version 1 work carries no sketch length
+    let mut buf = BytesMut::new();
+    legacy_codec
+        .encode(Message::Work(work_stack), &mut buf)
+        .unwrap();
+    assert_eq!(buf.len(), 1 + SKETCH_CAPACITY + HASH_LEN + 1);
+
+    // Decoding follows the negotiated version
+    let mut codec = MessageCodec::default();
+    codec.get_version().set(1);
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::Work(decoded)) => {
+            assert_eq!(decoded.get_root(), root);
+            assert_eq!(decoded.get_nonce(), 42);
+        }
+        _ => panic!("expected work"),
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_transactions_payload_limit() {
+    assert!(max_message_len(5) < MAX_FRAME_LEN);
+
+    let mut codec = MessageCodec::default();
+    let mut buf = BytesMut::new();
+    buf.reserve(1);
+    buf.put_u8(5);
+    buf.extend(Bytes::from(VarInt::new(MAX_TXS_PAYLOAD_LEN as u64 + 1)));
+    assert!(codec.decode(&mut buf).is_err());
+}
@@ -0,0 +1,7 @@
+mod addresses_tests;
+mod bans_tests;
+mod keepalive_tests;
+mod messages_tests;
+mod peers_tests;
+mod relay_tests;
+mod transport_tests;
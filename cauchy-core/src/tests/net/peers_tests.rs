@@ -0,0 +1,50 @@
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+
+use crate::{
+    ego::peer_ego::PeerEgo,
+    net::peers::{Peer, ADDR_V4, ADDR_V6},
+};
+
+#[test]
+fn test_peer_v4() {
+    let addr: SocketAddr = "127.0.0.1:8332".parse().unwrap();
+    let raw = Bytes::from(Peer::new(addr));
+    assert_eq!(raw[0], ADDR_V4);
+    assert_eq!(raw.len(), 1 + 4 + 2);
+    assert_eq!(Peer::try_from(raw).unwrap().get_addr(), addr);
+}
+
+#[test]
+fn test_peer_v6() {
+    let addr: SocketAddr = "[2001:db8::1]:8332".parse().unwrap();
+    let raw = Bytes::from(Peer::new(addr));
+    assert_eq!(raw[0], ADDR_V6);
+    assert_eq!(raw.len(), 1 + 16 + 2);
+    assert_eq!(Peer::try_from(raw).unwrap().get_addr(), addr);
+}
+
+#[test]
+fn test_peer_unknown_version() {
+    let raw = Bytes::from(&[5, 127, 0, 0, 1, 0, 80][..]);
+    assert!(Peer::try_from(raw).is_err());
+}
+
+#[test]
+fn test_peer_too_short() {
+    let raw = Bytes::from(&[ADDR_V6, 0, 0, 0, 0][..]);
+    assert!(Peer::try_from(raw).is_err());
+}
+
+#[test]
+fn test_peers_request() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    assert!(!peer_ego.take_peers_request());
+
+    // Each request admits a single reply
+    peer_ego.request_peers();
+    assert!(peer_ego.take_peers_request());
+    assert!(!peer_ego.take_peers_request());
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::codec::{Decoder, Encoder};
+
+use crate::{
+    crypto::sketches::odd_sketch::OddSketch,
+    ego::peer_ego::PeerEgo,
+    net::{
+        messages::{Message, MessageCodec},
+        relay::{batch_transactions, InventorySet},
+    },
+    primitives::{status::Expectation, transaction::Transaction, varint::VarInt},
+    utils::constants::{HASH_LEN, MAX_INVENTORY_PER_MSG, MAX_TXS_PAYLOAD_LEN},
+};
+
+fn id(i: u8) -> Bytes {
+    Bytes::from(vec![i; HASH_LEN])
+}
+
+#[test]
+fn test_inventory_set_eviction() {
+    let mut set = InventorySet::with_capacity(2);
+    assert!(set.insert(id(0)));
+    assert!(!set.insert(id(0)));
+    assert!(set.insert(id(1)));
+
+    // Oldest is forgotten once full
+    assert!(set.insert(id(2)));
+    assert_eq!(set.len(), 2);
+    assert!(!set.contains(&id(0)));
+    assert!(set.contains(&id(1)));
+    assert!(set.contains(&id(2)));
+}
+
+#[test]
+fn test_inventory_round_trip() {
+    let mut codec = MessageCodec::default();
+    let ids: HashSet<Bytes> = (0..3).map(id).collect();
+
+    let mut buf = BytesMut::new();
+    codec
+        .encode(Message::Inventory { ids: ids.clone() }, &mut buf)
+        .unwrap();
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::Inventory { ids: decoded }) => assert_eq!(decoded, ids),
+        _ => panic!("expected inventory"),
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_fulfil_requests() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    let requested: HashSet<Bytes> = (0..2).map(id).collect();
+    peer_ego.add_requests(&requested);
+
+    // Only replies made up entirely of requested transactions
+    assert!(!peer_ego.fulfil_requests(&[]));
+    assert!(!peer_ego.fulfil_requests(&[id(0), id(2)]));
+    assert!(peer_ego.fulfil_requests(&[id(0)]));
+    assert!(!peer_ego.fulfil_requests(&[id(0)]));
+    assert!(peer_ego.fulfil_requests(&[id(1)]));
+}
+
+#[test]
+fn test_sent_once() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    assert!(!peer_ego.was_sent(&id(0)));
+    peer_ego.mark_sent(id(0));
+    assert!(peer_ego.was_sent(&id(0)));
+    assert!(!peer_ego.was_sent(&id(1)));
+}
+
+#[test]
+fn test_inventory_limit() {
+    let mut codec = MessageCodec::default();
+    let mut buf = BytesMut::new();
+    buf.reserve(1);
+    buf.put_u8(14);
+    buf.extend(Bytes::from(VarInt::new(MAX_INVENTORY_PER_MSG as u64 + 1)));
+    buf.extend(Bytes::from(vec![0; HASH_LEN]));
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_reconciliation_ids() {
+    let mut expectation = Expectation::new(OddSketch::default(), Bytes::new());
+    assert!(!expectation.is_expected_ids(&[id(0)]));
+
+    // Only the whole expected payload is told apart from relay replies
+    expectation.update_ids((0..2).map(id).collect());
+    assert!(!expectation.is_expected_ids(&[id(0)]));
+    assert!(expectation.is_expected_ids(&[id(1), id(0)]));
+}
+
+#[test]
+fn test_request_expiry() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    let requested: HashSet<Bytes> = (0..2).map(id).collect();
+    peer_ego.add_requests(&requested);
+    assert_eq!(peer_ego.request_room(), MAX_INVENTORY_PER_MSG - 2);
+
+    peer_ego.expire_requests(Duration::from_secs(60));
+    assert_eq!(peer_ego.request_room(), MAX_INVENTORY_PER_MSG - 2);
+
+    // Unserved requests no longer take up room
+    peer_ego.expire_requests(Duration::from_millis(0));
+    assert_eq!(peer_ego.request_room(), MAX_INVENTORY_PER_MSG);
+    assert!(!peer_ego.fulfil_requests(&[id(0)]));
+}
+
+#[test]
+fn test_newly_announced() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    peer_ego.mark_announced(vec![id(0)]);
+    let ids = peer_ego.newly_announced((0..2).map(id).collect());
+    assert_eq!(ids, vec![id(1)]);
+    assert!(peer_ego
+        .newly_announced((0..2).map(id).collect())
+        .is_empty());
+}
+
+#[test]
+fn test_single_lookup() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    assert!(peer_ego.start_lookup());
+    assert!(!peer_ego.start_lookup());
+    peer_ego.end_lookup();
+    assert!(peer_ego.start_lookup());
+}
+
+#[test]
+fn test_batch_transactions() {
+    let tx = |len: usize| Transaction::new(0, Bytes::new(), Bytes::from(vec![0; len]));
+    let txs = vec![
+        tx(MAX_TXS_PAYLOAD_LEN / 2),
+        tx(MAX_TXS_PAYLOAD_LEN / 2),
+        tx(MAX_TXS_PAYLOAD_LEN + 1),
+        tx(1),
+    ];
+
+    // Each batch fits in a message, the oversized transaction is dropped
+    let batches = batch_transactions(txs);
+    let lens: Vec<usize> = batches.iter().map(Vec::len).collect();
+    assert_eq!(lens, vec![1, 2]);
+    for batch in batches {
+        let payload_len: usize = batch.into_iter().map(|tx| Bytes::from(tx).len()).sum();
+        assert!(payload_len <= MAX_TXS_PAYLOAD_LEN);
+    }
+}
@@ -0,0 +1,160 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::codec::{Decoder, Encoder};
+
+use crate::{
+    crypto::signatures::ecdsa::generate_keypair,
+    ego::peer_ego::PeerEgo,
+    net::{
+        messages::{max_message_len, Message, MAX_MESSAGE_TAG},
+        transport::{CipherState, SecureCodec, Session},
+    },
+    utils::constants::{MAX_FRAME_LEN, PROTOCOL_VERSION},
+};
+
+fn session_pair() -> (Session, Session) {
+    let (sk_a, pk_a) = generate_keypair();
+    let (sk_b, pk_b) = generate_keypair();
+    (
+        Session::new(&sk_a, &pk_a, &pk_b).unwrap(),
+        Session::new(&sk_b, &pk_b, &pk_a).unwrap(),
+    )
+}
+
+#[test]
+fn test_session_round_trip() {
+    let (mut session_a, mut session_b) = session_pair();
+    assert_eq!(session_a.id, session_b.id);
+
+    let mut buf = BytesMut::new();
+    session_a
+        .codec
+        .encode(Message::StartHandshake { secret: 42 }, &mut buf)
+        .unwrap();
+    session_a.codec.encode(Message::GetPeers, &mut buf).unwrap();
+
+    // Partial frames wait for more bytes
+    let mut partial = BytesMut::from(&buf[..3]);
+    assert!(session_b.codec.decode(&mut partial).unwrap().is_none());
+
+    match session_b.codec.decode(&mut buf).unwrap() {
+        Some(Message::StartHandshake { secret }) => assert_eq!(secret, 42),
+        _ => panic!("expected start handshake"),
+    }
+    match session_b.codec.decode(&mut buf).unwrap() {
+        Some(Message::GetPeers) => (),
+        _ => panic!("expected get peers"),
+    }
+    assert!(buf.is_empty());
+
+    // Replies use the other direction's key
+    let mut buf = BytesMut::new();
+    session_b.codec.encode(Message::GetWork, &mut buf).unwrap();
+    match session_a.codec.decode(&mut buf).unwrap() {
+        Some(Message::GetWork) => (),
+        _ => panic!("expected get work"),
+    }
+}
+
+#[test]
+fn test_tampered_frame() {
+    let (mut session_a, mut session_b) = session_pair();
+
+    let mut buf = BytesMut::new();
+    session_a.codec.encode(Message::GetPeers, &mut buf).unwrap();
+    let last = buf.len() - 1;
+    buf[last] ^= 1;
+    assert!(session_b.codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_replayed_frame() {
+    let (mut session_a, mut session_b) = session_pair();
+
+    let mut buf = BytesMut::new();
+    session_a.codec.encode(Message::GetPeers, &mut buf).unwrap();
+    let mut replay = buf.clone();
+    assert!(session_b.codec.decode(&mut buf).unwrap().is_some());
+    assert!(session_b.codec.decode(&mut replay).is_err());
+}
+
+#[test]
+fn test_distinct_sessions() {
+    let (session_a, _) = session_pair();
+    let (session_b, _) = session_pair();
+    assert_ne!(session_a.id, session_b.id);
+}
+
+#[test]
+fn test_reflected_key() {
+    let (sk, pk) = generate_keypair();
+    assert!(Session::new(&sk, &pk, &pk).is_err());
+}
+
+// Encrypts raw plaintexts into frames for a codec receiving under the key
+fn frames(key: &[u8], plaintexts: &[Vec<u8>]) -> (SecureCodec, BytesMut) {
+    let mut sender = CipherState::new(key);
+    let codec = SecureCodec::new(CipherState::new(&[0; 32]), CipherState::new(key));
+
+    let mut buf = BytesMut::new();
+    for plaintext in plaintexts {
+        let ciphertext = sender.encrypt(plaintext).unwrap();
+        buf.reserve(4 + ciphertext.len());
+        buf.put_u32_be(ciphertext.len() as u32);
+        buf.extend_from_slice(&ciphertext);
+    }
+    (codec, buf)
+}
+
+#[test]
+fn test_unknown_message_skipped() {
+    let (mut codec, mut buf) = frames(&[1; 32], &[vec![MAX_MESSAGE_TAG + 1, 1, 2, 3], vec![10]]);
+
+    // Messages from newer versions don't end the connection
+    match codec.decode(&mut buf).unwrap() {
+        Some(Message::GetPeers) => (),
+        _ => panic!("expected get peers"),
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_negotiated_version() {
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    assert_eq!(peer_ego.get_version(), None);
+
+    // Newer peers speak our version
+    peer_ego.set_version(PROTOCOL_VERSION + 1, !0);
+    assert_eq!(peer_ego.get_version(), Some(PROTOCOL_VERSION));
+    assert!(peer_ego.supports(0));
+}
+
+#[test]
+fn test_trailing_garbage() {
+    let (mut codec, mut buf) = frames(&[1; 32], &[vec![10, 0]]);
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_empty_frame() {
+    let (mut codec, mut buf) = frames(&[1; 32], &[vec![]]);
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_oversized_message() {
+    // Start handshake tag followed by padding
+    let plaintext = vec![0; max_message_len(0) + 1];
+    let (mut codec, mut buf) = frames(&[1; 32], &[plaintext]);
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[test]
+fn test_oversized_frame() {
+    let (mut codec, _) = frames(&[1; 32], &[]);
+
+    // Rejected from the length prefix alone
+    let mut buf = BytesMut::new();
+    buf.reserve(4);
+    buf.put_u32_be(MAX_FRAME_LEN as u32 + 1);
+    assert!(codec.decode(&mut buf).is_err());
+}
@@ -0,0 +1,82 @@
+mod serialisation {
+    use bytes::{Bytes, IntoBuf};
+
+    use crate::{
+        primitives::{
+            access_pattern::AccessPattern,
+            act::{Act, Event, Message},
+        },
+        utils::{parsing::*, serialisation::*},
+    };
+
+    fn generate_access_pattern() -> AccessPattern {
+        let mut access_pattern = AccessPattern::default();
+        access_pattern.read.insert(Bytes::from(&b"key_b"[..]));
+        access_pattern.read.insert(Bytes::from(&b"key_a"[..]));
+        access_pattern
+            .write
+            .insert(Bytes::from(&b"key_c"[..]), Bytes::from(&b"long value"[..]));
+        access_pattern
+            .write
+            .insert(Bytes::from(&b"key_a"[..]), Bytes::from(&b"v"[..]));
+        access_pattern
+    }
+
+    #[test]
+    fn test_access_pattern_serialise() {
+        let raw = &b"\x02\x05key_a\x05key_b\x02\x05key_a\x01v\x05key_c\x0along value"[..];
+        assert_eq!(Bytes::from(generate_access_pattern()), Bytes::from(raw))
+    }
+
+    #[test]
+    fn test_access_pattern_serialise_deserialise() {
+        let access_pattern = generate_access_pattern();
+        let raw = Bytes::from(access_pattern.clone());
+        let (access_pattern_b, len) = AccessPattern::parse_buf(&mut raw.clone().into_buf())
+            .unwrap()
+            .unwrap();
+        assert_eq!(access_pattern, access_pattern_b);
+        assert_eq!(len, raw.len());
+    }
+
+    #[test]
+    fn test_access_pattern_non_canonical() {
+        let raw = Bytes::from(&b"\x02\x05key_b\x05key_a\x00"[..]);
+        assert!(AccessPattern::parse_buf(&mut raw.into_buf()).is_err())
+    }
+
+    #[test]
+    fn test_access_pattern_too_short() {
+        let raw = Bytes::from(&b"\x02\x05key_a\x05ke"[..]);
+        assert!(AccessPattern::parse_buf(&mut raw.into_buf())
+            .unwrap()
+            .is_none())
+    }
+
+    #[test]
+    fn test_act_serialise_deserialise() {
+        let messages = vec![
+            Message::new(
+                Bytes::from(&b"sender"[..]),
+                Bytes::from(&b"receiver"[..]),
+                Bytes::from(&b"payload"[..]),
+            ),
+            Message::new(
+                Bytes::from(&b"receiver"[..]),
+                Bytes::from(&b"sender"[..]),
+                Bytes::from(&b""[..]),
+            ),
+        ];
+        let events = vec![
+            Event::new(Bytes::from(&b"topic"[..]), Bytes::from(&b"data"[..])),
+            Event::new(Bytes::from(&b"topic"[..]), Bytes::from(&b""[..])),
+        ];
+        let act = Act::new(generate_access_pattern(), messages, events, 1234);
+        let raw = Bytes::from(act.clone());
+        let (act_b, len) = Act::parse_buf(&mut raw.clone().into_buf())
+            .unwrap()
+            .unwrap();
+        assert_eq!(act, act_b);
+        assert_eq!(len, raw.len());
+    }
+}
@@ -0,0 +1,139 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    crypto::{signatures::ecdsa::generate_keypair, sketches::odd_sketch::OddSketch},
+    ego::{ego::Ego, peer_ego::PeerEgo},
+    net::bans::Misbehaviour,
+    primitives::{
+        arena::Arena,
+        status::{Expectation, PeerStatus, Status},
+    },
+    utils::constants::Timeouts,
+};
+
+fn generate_arena() -> (Arena, Arc<Mutex<Ego>>) {
+    let (sk, pk) = generate_keypair();
+    let ego = Arc::new(Mutex::new(Ego::new(pk, sk)));
+    (Arena::new(ego.clone()), ego)
+}
+
+fn add_peer(arena: &mut Arena, status: PeerStatus) -> Arc<Mutex<PeerEgo>> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], 8000 + arena.n_peers() as u16));
+    let (mut peer_ego, _peer_stream) = PeerEgo::new(Bytes::new());
+    peer_ego.update_status(status);
+    let arc_peer_ego = Arc::new(Mutex::new(peer_ego));
+    arena.new_peer(&addr, arc_peer_ego.clone(), false);
+    arc_peer_ego
+}
+
+fn expired_timeouts() -> Timeouts {
+    Timeouts {
+        work_pull_ms: Duration::from_millis(0),
+        state_pull_ms: Duration::from_millis(0),
+        state_push_ms: Duration::from_millis(0),
+        pulling_ms: Duration::from_millis(0),
+        handshake_ms: Duration::from_millis(0),
+    }
+}
+
+#[test]
+fn test_handshakes_inbound() {
+    let (mut arena, _) = generate_arena();
+    arena.start_handshake();
+    arena.start_handshake();
+    assert_eq!(arena.n_inbound(), 2);
+
+    // Finished handshakes no longer count
+    arena.end_handshake();
+    arena.end_handshake();
+    arena.end_handshake();
+    assert_eq!(arena.n_inbound(), 0);
+}
+
+#[test]
+fn test_work_pull_timeout() {
+    let (mut arena, _) = generate_arena();
+    let peer_ego = add_peer(&mut arena, PeerStatus::WorkPull);
+
+    // Peers are left alone until their timeout
+    arena.timeout_sweep(&Timeouts::default());
+    assert!(peer_ego.lock().unwrap().get_status() == PeerStatus::WorkPull);
+    assert_eq!(peer_ego.lock().unwrap().get_score(), 0);
+
+    arena.timeout_sweep(&expired_timeouts());
+    let peer_ego_guard = peer_ego.lock().unwrap();
+    assert!(peer_ego_guard.get_status() == PeerStatus::Idle);
+    assert_eq!(
+        peer_ego_guard.get_score(),
+        Misbehaviour::WorkTimeout.penalty()
+    );
+    assert_eq!(
+        arena.get_timeouts().get(PeerStatus::WorkPull.to_str()),
+        Some(&1)
+    );
+}
+
+#[test]
+fn test_state_pull_timeout() {
+    let (mut arena, ego) = generate_arena();
+    ego.lock().unwrap().update_status(Status::Pulling);
+    let expectation = Expectation::new(OddSketch::default(), Bytes::new());
+    let peer_ego = add_peer(&mut arena, PeerStatus::StatePull(expectation));
+
+    // Abandoning reconciliation with the peer releases the ego
+    let mut timeouts = expired_timeouts();
+    timeouts.pulling_ms = Duration::from_secs(60);
+    arena.timeout_sweep(&timeouts);
+    let peer_ego_guard = peer_ego.lock().unwrap();
+    assert!(peer_ego_guard.get_status() == PeerStatus::Idle);
+    assert_eq!(
+        peer_ego_guard.get_score(),
+        Misbehaviour::ReconcileTimeout.penalty()
+    );
+    assert!(ego.lock().unwrap().get_status() == Status::Idle);
+}
+
+#[test]
+fn test_received_payload_exempt() {
+    let (mut arena, ego) = generate_arena();
+    ego.lock().unwrap().update_status(Status::Pulling);
+    let mut expectation = Expectation::new(OddSketch::default(), Bytes::new());
+    expectation.mark_received();
+    let peer_ego = add_peer(&mut arena, PeerStatus::StatePull(expectation.clone()));
+
+    // Ingest has the payload, neither the peer nor the ego are reset
+    arena.timeout_sweep(&expired_timeouts());
+    let peer_ego_guard = peer_ego.lock().unwrap();
+    assert!(peer_ego_guard.get_status() == PeerStatus::StatePull(expectation));
+    assert_eq!(peer_ego_guard.get_score(), 0);
+    assert!(ego.lock().unwrap().get_status() == Status::Pulling);
+}
+
+#[test]
+fn test_state_push_timeout() {
+    let (mut arena, _) = generate_arena();
+    let peer_ego = add_peer(&mut arena, PeerStatus::StatePush);
+
+    // Pushing peers are reset without penalty
+    arena.timeout_sweep(&expired_timeouts());
+    let peer_ego_guard = peer_ego.lock().unwrap();
+    assert!(peer_ego_guard.get_status() == PeerStatus::Idle);
+    assert_eq!(peer_ego_guard.get_score(), 0);
+}
+
+#[test]
+fn test_pulling_timeout() {
+    let (mut arena, ego) = generate_arena();
+    ego.lock().unwrap().update_status(Status::Pulling);
+
+    arena.timeout_sweep(&Timeouts::default());
+    assert!(ego.lock().unwrap().get_status() == Status::Pulling);
+
+    arena.timeout_sweep(&expired_timeouts());
+    assert!(ego.lock().unwrap().get_status() == Status::Idle);
+    assert_eq!(arena.get_timeouts().get(Status::Pulling.to_str()), Some(&1));
+}
@@ -0,0 +1,71 @@
+mod serialisation {
+    use std::convert::TryFrom;
+
+    use bytes::Bytes;
+
+    use crate::{
+        primitives::call::{selector, Argument, Call, MAX_ARGUMENTS},
+        utils::serialisation::*,
+    };
+
+    fn generate_call() -> Call {
+        Call::new(
+            "transfer",
+            vec![
+                Argument::Id(Bytes::from(&[0x41; 32][..])),
+                Argument::U64(1000),
+                Argument::U32(7),
+                Argument::Bytes(Bytes::from(&b"memo"[..])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_selector() {
+        assert_eq!(generate_call().get_selector(), selector("transfer"));
+        assert!(generate_call().is_method("transfer"));
+        assert!(!generate_call().is_method("mint"));
+    }
+
+    #[test]
+    fn test_serialise() {
+        let raw = Bytes::from(generate_call());
+        assert_eq!(raw[..4], selector("transfer").to_le_bytes()[..]);
+        assert_eq!(raw[4], 4);
+        assert_eq!(raw[5], 3);
+        assert_eq!(raw.len(), 5 + (1 + 32) + (1 + 8) + (1 + 4) + (1 + 4 + 4));
+    }
+
+    #[test]
+    fn test_serialise_deserialise() {
+        let call = generate_call();
+        let call_b = Call::try_from(Bytes::from(call.clone())).unwrap();
+        assert_eq!(call, call_b)
+    }
+
+    #[test]
+    fn test_unknown_tag() {
+        let raw = Bytes::from(&b"\x00\x00\x00\x00\x01\x09"[..]);
+        assert!(Call::try_from(raw).is_err())
+    }
+
+    #[test]
+    fn test_too_short() {
+        let mut raw = Bytes::from(generate_call()).to_vec();
+        raw.pop();
+        assert!(Call::try_from(Bytes::from(raw)).is_err())
+    }
+
+    #[test]
+    fn test_invalid_id_length() {
+        let id = Bytes::from(&[0x41; 31][..]);
+        assert!(Call::new("transfer", vec![Argument::Id(id)]).is_err())
+    }
+
+    #[test]
+    fn test_too_many_arguments() {
+        let arguments = vec![Argument::U32(0); MAX_ARGUMENTS + 1];
+        assert!(Call::new("transfer", arguments).is_err())
+    }
+}
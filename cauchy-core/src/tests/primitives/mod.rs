@@ -1,3 +1,6 @@
+mod act_tests;
+mod arena_tests;
+mod call_tests;
 mod transaction_tests;
 mod tx_pool_tests;
 mod varint_tests;
@@ -1,18 +1,7 @@
-use bytes::Bytes;
-use rand::Rng;
-
 use crate::{
-    primitives::{transaction::Transaction, tx_pool::TxPool},
-    utils::errors::TxPoolError,
+    primitives::tx_pool::TxPool, tests::helpers::generate_random_tx, utils::errors::TxPoolError,
 };
 
-fn generate_random_tx(time: u64) -> Transaction {
-    let mut rng = rand::thread_rng();
-    let aux_data: [u8; 8] = rng.gen();
-    let binary: [u8; 8] = rng.gen();
-    Transaction::new(time, Bytes::from(&aux_data[..]), Bytes::from(&binary[..]))
-}
-
 #[test]
 fn test_put_sorted() {
     let tx_a = generate_random_tx(0);
@@ -48,3 +37,22 @@ fn test_put_unsorted() {
 
     assert!(mempool.insert_batch(vec![tx_a, tx_c, tx_b], true).is_err())
 }
+
+#[test]
+fn test_drain_ready() {
+    let tx_a = generate_random_tx(0);
+    let tx_b = generate_random_tx(1);
+    let tx_c = generate_random_tx(2);
+
+    let mut mempool = TxPool::with_capacity(3);
+    mempool
+        .insert_batch(vec![tx_c.clone(), tx_a.clone(), tx_b.clone()], false)
+        .unwrap();
+
+    // Drains in timestamp order
+    assert_eq!(mempool.drain_ready(1), vec![tx_a, tx_b]);
+    assert_eq!(mempool.len(), 1);
+
+    assert_eq!(mempool.drain_ready(2), vec![tx_c]);
+    assert!(mempool.is_empty());
+}
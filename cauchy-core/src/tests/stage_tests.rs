@@ -0,0 +1,419 @@
+mod peer_validation {
+    use std::collections::HashSet;
+
+    use bytes::Bytes;
+
+    use crate::{
+        crypto::{
+            hashes::Identifiable,
+            sketches::{dummy_sketch::DummySketch, odd_sketch::OddSketch, Sketchable},
+        },
+        primitives::{status::Expectation, transaction::Transaction},
+        stage::validate_peer_txs,
+        tests::helpers::generate_random_tx,
+        utils::constants::HASH_LEN,
+    };
+
+    // Local node has shared and excess transactions, peer has shared and missing transactions
+    fn generate_state(
+        shared: &[Transaction],
+        excess: &[Transaction],
+        missing: &[Transaction],
+    ) -> (OddSketch, DummySketch, Expectation) {
+        let local: Vec<Transaction> = shared.iter().chain(excess.iter()).cloned().collect();
+        let peer: Vec<Transaction> = shared.iter().chain(missing.iter()).cloned().collect();
+
+        let mut expectation =
+            Expectation::new(OddSketch::sketch(&peer), Bytes::from(&[0; HASH_LEN][..]));
+        expectation.update_ids(
+            missing
+                .iter()
+                .map(|tx| tx.get_id())
+                .collect::<HashSet<Bytes>>(),
+        );
+        expectation.update_minisketch(DummySketch::sketch(&peer));
+        (
+            OddSketch::sketch(&local),
+            DummySketch::sketch(&local),
+            expectation,
+        )
+    }
+
+    #[test]
+    fn test_valid() {
+        let shared = vec![generate_random_tx(0), generate_random_tx(1)];
+        let excess = vec![generate_random_tx(2)];
+        let missing = vec![generate_random_tx(3), generate_random_tx(4)];
+        let (oddsketch, minisketch, expectation) = generate_state(&shared, &excess, &missing);
+        let (expected_minisketch, rolled_back) =
+            validate_peer_txs(&expectation, &missing, oddsketch, minisketch).unwrap();
+        assert_eq!(Some(expected_minisketch), expectation.get_minisketch());
+        assert_eq!(
+            rolled_back,
+            excess
+                .iter()
+                .map(|tx| tx.get_id())
+                .collect::<HashSet<Bytes>>()
+        )
+    }
+
+    #[test]
+    fn test_unexpected_payload() {
+        let shared = vec![generate_random_tx(0)];
+        let missing = vec![generate_random_tx(1), generate_random_tx(2)];
+        let (oddsketch, minisketch, expectation) = generate_state(&shared, &[], &missing);
+        assert!(validate_peer_txs(&expectation, &missing[..1], oddsketch, minisketch).is_none())
+    }
+
+    #[test]
+    fn test_inconsistent_minisketch() {
+        let shared = vec![generate_random_tx(0)];
+        let missing = vec![generate_random_tx(1)];
+        let (oddsketch, minisketch, mut expectation) = generate_state(&shared, &[], &missing);
+
+        // Peer reports a transaction it never sends
+        let mut peer = shared.clone();
+        peer.push(missing[0].clone());
+        peer.push(generate_random_tx(2));
+        expectation.update_minisketch(DummySketch::sketch(&peer));
+        assert!(validate_peer_txs(&expectation, &missing, oddsketch, minisketch).is_none())
+    }
+}
+
+mod priority {
+    use std::collections::HashSet;
+
+    use bytes::Bytes;
+
+    use crate::{
+        daemon::Priority,
+        primitives::status::Status,
+        stage::{accepts_peer_state, schedule_local, Schedule},
+    };
+
+    #[test]
+    fn test_schedule_idle() {
+        assert_eq!(
+            schedule_local(&Status::Idle, Priority::Standard),
+            Schedule::Now
+        );
+        assert_eq!(
+            schedule_local(&Status::Idle, Priority::Force),
+            Schedule::Now
+        );
+    }
+
+    #[test]
+    fn test_standard_queues_behind_reconciliation() {
+        assert_eq!(
+            schedule_local(&Status::Pulling, Priority::Standard),
+            Schedule::Queue
+        );
+    }
+
+    #[test]
+    fn test_force_preempts_reconciliation() {
+        assert_eq!(
+            schedule_local(&Status::Pulling, Priority::Force),
+            Schedule::Preempt
+        );
+    }
+
+    #[test]
+    fn test_standard_rejects_rollback() {
+        let mut excess = HashSet::new();
+        assert!(accepts_peer_state(Priority::Standard, &excess));
+        excess.insert(Bytes::from(&b"provisional"[..]));
+        assert!(!accepts_peer_state(Priority::Standard, &excess));
+    }
+
+    #[test]
+    fn test_force_accepts_rollback() {
+        let mut excess = HashSet::new();
+        excess.insert(Bytes::from(&b"provisional"[..]));
+        assert!(accepts_peer_state(Priority::Force, &excess));
+    }
+}
+
+mod queue {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    use bus::Bus;
+    use bytes::Bytes;
+
+    use crate::{
+        crypto::{hashes::Identifiable, signatures::ecdsa::generate_keypair},
+        daemon::Priority,
+        db::{mongodb::MongoDB, Database},
+        ego::ego::Ego,
+        primitives::{status::Status, tx_pool::TxPool},
+        stage::Stage,
+        tests::helpers::generate_random_tx,
+    };
+
+    // Stage whose ego is reconciling
+    fn generate_pulling_stage() -> (Stage, Arc<Mutex<Ego>>) {
+        let (sk, pk) = generate_keypair();
+        let mut ego = Ego::new(pk, sk);
+        ego.update_status(Status::Pulling);
+        let ego = Arc::new(Mutex::new(ego));
+        let db = MongoDB::open_db("tests_stage_queue").unwrap();
+        (Stage::new(ego.clone(), db, Bus::new(10)), ego)
+    }
+
+    #[test]
+    fn test_standard_returns_to_mempool() {
+        let (stage, ego) = generate_pulling_stage();
+        let mempool = Arc::new(Mutex::new(TxPool::with_capacity(8)));
+        let txs = vec![generate_random_tx(0), generate_random_tx(1)];
+
+        assert!(stage
+            .process_local_txs(txs.clone(), Priority::Standard, &HashSet::new(), &mempool)
+            .is_none());
+        assert!(ego.lock().unwrap().get_status() == Status::Pulling);
+        assert_eq!(mempool.lock().unwrap().drain_ready(1), txs);
+    }
+
+    #[test]
+    fn test_full_mempool_rejects() {
+        let (stage, _ego) = generate_pulling_stage();
+        let mempool = Arc::new(Mutex::new(TxPool::with_capacity(1)));
+        let txs = vec![generate_random_tx(0), generate_random_tx(1)];
+        let in_flight = stage.get_in_flight();
+        in_flight
+            .lock()
+            .unwrap()
+            .extend(txs.iter().map(|tx| tx.get_id()));
+
+        assert!(stage
+            .process_local_txs(txs, Priority::Standard, &HashSet::new(), &mempool)
+            .is_none());
+        assert!(mempool.lock().unwrap().is_empty());
+        assert!(in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_force_preempts() {
+        let (stage, ego) = generate_pulling_stage();
+        let mempool = Arc::new(Mutex::new(TxPool::with_capacity(8)));
+        let txs = vec![generate_random_tx(0), generate_random_tx(1)];
+
+        let processed =
+            stage.process_local_txs(txs.clone(), Priority::Force, &HashSet::new(), &mempool);
+        assert_eq!(processed, Some(txs));
+        assert!(ego.lock().unwrap().get_status() == Status::Idle);
+        assert!(mempool.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stored_rejected() {
+        let (stage, _ego) = generate_pulling_stage();
+        let mempool = Arc::new(Mutex::new(TxPool::with_capacity(8)));
+        let tx_a = generate_random_tx(0);
+        let tx_b = generate_random_tx(1);
+        let stored: HashSet<Bytes> = vec![tx_a.get_id()].into_iter().collect();
+
+        let processed =
+            stage.process_local_txs(vec![tx_a, tx_b.clone()], Priority::Force, &stored, &mempool);
+        assert_eq!(processed, Some(vec![tx_b]));
+    }
+}
+
+mod duplicates {
+    use crate::{stage::filter_duplicates, tests::helpers::generate_random_tx};
+
+    #[test]
+    fn test_repeated_in_batch() {
+        let tx_a = generate_random_tx(0);
+        let tx_b = generate_random_tx(1);
+        let txs = vec![tx_a.clone(), tx_b.clone(), tx_a.clone()];
+        assert_eq!(filter_duplicates(txs, |_| false), vec![tx_a, tx_b])
+    }
+
+    #[test]
+    fn test_already_stored() {
+        let tx_a = generate_random_tx(0);
+        let tx_b = generate_random_tx(1);
+        let stored = tx_a.clone();
+        let txs = vec![tx_a, tx_b.clone()];
+        assert_eq!(filter_duplicates(txs, |tx| *tx == stored), vec![tx_b])
+    }
+}
+
+mod revert {
+    use std::collections::HashSet;
+
+    use bson::spec::BinarySubtype;
+    use bson::{bson, doc, Bson};
+    use bytes::Bytes;
+
+    use crate::{
+        crypto::hashes::Identifiable,
+        db::{mongodb::MongoDB, storing::Storable, DataType, Database},
+        primitives::transaction::Transaction,
+        stage::revert_txs,
+        tests::helpers::generate_random_tx,
+    };
+
+    fn put_state(db: &MongoDB, tx: &Transaction, value: &[u8]) {
+        db.put(
+            &DataType::State,
+            doc! {
+                "t" : Bson::Binary(BinarySubtype::Generic, vec![1; 32]),
+                "o" : Bson::Binary(BinarySubtype::Generic, tx.get_id().to_vec()),
+                "k" : Bson::Binary(BinarySubtype::Generic, b"key".to_vec()),
+                "v" : Bson::Binary(BinarySubtype::Generic, value.to_vec()),
+            },
+        )
+        .unwrap();
+    }
+
+    fn get_state(db: &MongoDB, tx: &Transaction) -> Option<Vec<u8>> {
+        db.get(
+            &DataType::State,
+            doc! { "o" : Bson::Binary(BinarySubtype::Generic, tx.get_id().to_vec()) },
+        )
+        .unwrap()
+        .map(|found_doc| found_doc.get_binary_generic("v").unwrap().to_vec())
+    }
+
+    #[test]
+    fn test_revert_skips_unstored() {
+        let mut db = MongoDB::open_db("tests_stage_revert").unwrap();
+        db.dropall(&DataType::TX);
+        db.dropall(&DataType::State);
+
+        let reverted = generate_random_tx(0);
+        let unstored = generate_random_tx(1);
+        reverted.to_db(&mut db, None).unwrap();
+        put_state(&db, &reverted, b"a");
+
+        let ids: HashSet<Bytes> = vec![reverted.get_id(), unstored.get_id()]
+            .into_iter()
+            .collect();
+        assert_eq!(revert_txs(&db, &ids), vec![reverted.clone()]);
+        assert!(get_state(&db, &reverted).is_none());
+        assert!(Transaction::from_db(&mut db, reverted.get_id())
+            .unwrap()
+            .is_none());
+    }
+
+    // Dependent transactions are not re-executed, their writes outlive the revert
+    #[test]
+    fn test_revert_keeps_dependents() {
+        let mut db = MongoDB::open_db("tests_stage_revert_dependents").unwrap();
+        db.dropall(&DataType::TX);
+        db.dropall(&DataType::State);
+
+        let reverted = generate_random_tx(0);
+        let dependent = generate_random_tx(1);
+        reverted.to_db(&mut db, None).unwrap();
+        dependent.to_db(&mut db, None).unwrap();
+        put_state(&db, &reverted, b"a");
+        put_state(&db, &dependent, b"derived from a");
+
+        let ids: HashSet<Bytes> = vec![reverted.get_id()].into_iter().collect();
+        assert_eq!(revert_txs(&db, &ids), vec![reverted]);
+        assert_eq!(get_state(&db, &dependent), Some(b"derived from a".to_vec()));
+        assert!(Transaction::from_db(&mut db, dependent.get_id())
+            .unwrap()
+            .is_some());
+    }
+}
+
+mod events {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::{Future, Stream};
+
+    use crate::{
+        stage::events::{EventBus, Rejection, StageEvent},
+        utils::constants::EVENT_CHANNEL_CAPACITY,
+        vm::performance::Performance,
+    };
+
+    #[test]
+    fn test_publish_subscribe() {
+        let bus = EventBus::default();
+        let recv_a = bus.subscribe();
+        let recv_b = bus.subscribe();
+
+        let event = StageEvent::Rejected {
+            tx_id: Bytes::from(&b"tx"[..]),
+            reason: Rejection::Duplicate,
+        };
+        bus.publish(event.clone());
+
+        let (received, _) = recv_a.into_future().wait().ok().unwrap();
+        assert_eq!(received, Some(event.clone()));
+        let (received, _) = recv_b.into_future().wait().ok().unwrap();
+        assert_eq!(received, Some(event));
+    }
+
+    #[test]
+    fn test_dropped_subscriber() {
+        let bus = EventBus::default();
+        let recv = bus.subscribe();
+        assert_eq!(bus.n_subscribers(), 1);
+
+        drop(recv);
+        bus.publish(StageEvent::Accepted {
+            tx_id: Bytes::from(&b"tx"[..]),
+        });
+        assert_eq!(bus.n_subscribers(), 0);
+    }
+
+    #[test]
+    fn test_lagging_subscriber() {
+        let bus = EventBus::default();
+        let recv = bus.subscribe();
+
+        let n_published = 2 * EVENT_CHANNEL_CAPACITY;
+        for _ in 0..n_published {
+            bus.publish(StageEvent::Accepted {
+                tx_id: Bytes::from(&b"tx"[..]),
+            });
+        }
+        assert_eq!(bus.n_subscribers(), 1);
+
+        let n_dropped = bus.n_dropped();
+        assert!(n_dropped > 0);
+        drop(bus);
+        let received: Vec<StageEvent> = recv.collect().wait().unwrap();
+        assert_eq!(received.len() + n_dropped, n_published);
+    }
+
+    #[test]
+    fn test_state_changes() {
+        let bus = EventBus::default();
+        let recv = bus.subscribe();
+
+        let actor_id = Bytes::from(&b"actor"[..]);
+        let mut performance = Performance::default();
+        performance.add_write(
+            &actor_id,
+            Bytes::from(&b"key"[..]),
+            Bytes::from(&b"value"[..]),
+        );
+        bus.publish_performance(Bytes::from(&b"tx"[..]), &performance);
+        drop(bus);
+
+        let received: Vec<StageEvent> = recv.collect().wait().unwrap();
+        assert_eq!(
+            received,
+            vec![
+                StageEvent::StateChanged {
+                    actor_id,
+                    key: Bytes::from(&b"key"[..]),
+                    value: Bytes::from(&b"value"[..]),
+                },
+                StageEvent::Performed {
+                    tx_id: Bytes::from(&b"tx"[..]),
+                    performance: Arc::new(performance),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::env::temp_dir;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::utils::identity::*;
+
+fn test_path(name: &str) -> PathBuf {
+    let mut path = temp_dir();
+    path.push(format!("cauchy_identity_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path.push("identity.key");
+    path
+}
+
+#[test]
+fn test_load_persists() {
+    let path = test_path("load");
+    let (_, pk) = load_identity(&path).unwrap();
+    let (_, pk_reloaded) = load_identity(&path).unwrap();
+    assert_eq!(pk, pk_reloaded);
+}
+
+#[test]
+fn test_generate_exists() {
+    let path = test_path("generate");
+    let pk = generate_identity(&path).unwrap();
+    assert_eq!(read_identity(&path).unwrap().unwrap().1, pk);
+    assert!(generate_identity(&path).is_err());
+}
+
+#[test]
+fn test_rotate() {
+    let path = test_path("rotate");
+    assert!(rotate_identity(&path).is_err());
+
+    let pk = generate_identity(&path).unwrap();
+    let rotated_pk = rotate_identity(&path).unwrap();
+    assert_ne!(pk, rotated_pk);
+    assert_eq!(read_identity(&path).unwrap().unwrap().1, rotated_pk);
+    assert_eq!(
+        read_identity(&path.with_extension("old"))
+            .unwrap()
+            .unwrap()
+            .1,
+        pk
+    );
+
+    // The previous key is never overwritten
+    assert!(rotate_identity(&path).is_err());
+    assert_eq!(read_identity(&path).unwrap().unwrap().1, rotated_pk);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = test_path("permissions");
+    generate_identity(&path).unwrap();
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stale_tmp_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = test_path("stale");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, b"stale").unwrap();
+    fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    generate_identity(&path).unwrap();
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(!tmp_path.exists());
+}
@@ -1 +1,2 @@
 mod byte_op_tests;
+mod identity_tests;
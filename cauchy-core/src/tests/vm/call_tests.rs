@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::sync::{mpsc, oneshot};
+
+use crate::{
+    db::{mongodb::MongoDB, *},
+    primitives::{
+        call::{Argument, Call},
+        transaction::Transaction,
+    },
+    utils::constants::MachineFlavour,
+    vm::{performance::Performance, Mailbox, VM},
+};
+
+// The decoder scripts_rust builds into its script
+#[path = "scripts_rust/src/call.rs"]
+#[allow(dead_code)]
+mod script_call;
+
+// Runs the decoding script, returning its exit code
+fn run_call(aux: Bytes) -> u8 {
+    let db = MongoDB::open_db("test_call").unwrap();
+    let mut file = File::open("src/tests/vm/scripts/call_decode").unwrap();
+    let mut script = Vec::new();
+    file.read_to_end(&mut script).unwrap();
+
+    let tx = Transaction::new(407548800, aux, Bytes::from(script));
+    let (outbox, _outbox_recv) = mpsc::channel(512);
+    let (mailbox, _inbox_send) = Mailbox::new(outbox);
+    let (parent_branch, _) = oneshot::channel();
+    let vm = VM::with_flavour(db, MachineFlavour::Sparse);
+    vm.run(
+        mailbox,
+        tx,
+        Bytes::new(),
+        Arc::new(Mutex::new(Performance::default())),
+        parent_branch,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_decode_call() {
+    let call = Call::new(
+        "transfer",
+        vec![
+            Argument::U64(42),
+            Argument::Id(Bytes::from(&[0x41; 32][..])),
+        ],
+    )
+    .unwrap();
+    assert_eq!(run_call(Bytes::from(call)), 42);
+}
+
+#[test]
+fn test_decode_wrong_method() {
+    let call = Call::new("mint", vec![Argument::U64(42)]).unwrap();
+    assert_eq!(run_call(Bytes::from(call)), 255);
+}
+
+#[test]
+fn test_decode_wrong_argument() {
+    let call = Call::new("transfer", vec![Argument::U32(42)]).unwrap();
+    assert_eq!(run_call(Bytes::from(call)), 255);
+}
+
+#[test]
+fn test_script_decoder() {
+    let call = Call::new(
+        "transfer",
+        vec![
+            Argument::U32(7),
+            Argument::U64(1 << 40),
+            Argument::Bytes(Bytes::from(&b"memo"[..])),
+            Argument::Id(Bytes::from(&[0x41; 32][..])),
+        ],
+    )
+    .unwrap();
+    let raw = Bytes::from(call.clone());
+    let mut decoded = script_call::Call::new(&raw).unwrap();
+    assert_eq!(decoded.selector(), call.get_selector());
+    assert_eq!(decoded.n_args(), 4);
+    assert_eq!(decoded.next_u32(), Some(7));
+    assert_eq!(decoded.next_u64(), Some(1 << 40));
+    assert_eq!(decoded.next_bytes(), Some(&b"memo"[..]));
+    assert_eq!(decoded.next_id(), Some(&[0x41; 32][..]));
+    assert_eq!(decoded.next_u32(), None);
+}
+
+#[test]
+fn test_script_decoder_truncated() {
+    // Bytes argument claiming more data than remains
+    let raw = [0, 0, 0, 0, 1, 2, 0xff, 0xff, 0xff, 0xff, 0];
+    let mut decoded = script_call::Call::new(&raw).unwrap();
+    assert_eq!(decoded.next_bytes(), None);
+
+    // Wrong tag leaves the argument in place
+    let raw = [0, 0, 0, 0, 1, 1, 5, 0, 0, 0, 0, 0, 0, 0];
+    let mut decoded = script_call::Call::new(&raw).unwrap();
+    assert_eq!(decoded.next_u32(), None);
+    assert_eq!(decoded.next_u64(), Some(5));
+
+    // Truncated fixed width argument
+    let raw = [0, 0, 0, 0, 1, 1, 5, 0, 0, 0];
+    let mut decoded = script_call::Call::new(&raw).unwrap();
+    assert_eq!(decoded.next_u64(), None);
+}
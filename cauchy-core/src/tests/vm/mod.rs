@@ -1 +1,4 @@
+mod call_tests;
+mod performance_tests;
 mod test_simple;
+mod validation_tests;
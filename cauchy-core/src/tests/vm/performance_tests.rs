@@ -0,0 +1,58 @@
+mod serialisation {
+    use std::convert::TryFrom;
+
+    use bytes::Bytes;
+
+    use crate::{
+        crypto::hashes::Identifiable,
+        primitives::act::{Act, Event, Message},
+        utils::serialisation::*,
+        vm::performance::Performance,
+    };
+
+    fn generate_performance(order: &[&'static [u8]]) -> Performance {
+        let mut performance = Performance::default();
+        for id in order {
+            let id = Bytes::from(*id);
+            performance.add_read(&id, Bytes::from(&b"read"[..]));
+            performance.add_write(&id, Bytes::from(&b"write"[..]), id.clone());
+            performance.append(
+                id.clone(),
+                Act::new(
+                    Default::default(),
+                    vec![Message::new(id.clone(), id.clone(), id.clone())],
+                    vec![Event::new(id.clone(), id.clone())],
+                    1,
+                ),
+            );
+        }
+        performance
+    }
+
+    #[test]
+    fn test_serialise_deserialise() {
+        let performance = generate_performance(&[b"actor_a", b"actor_b", b"actor_c"]);
+        let raw = Bytes::from(performance.clone());
+        let performance_b = Performance::try_from(raw).unwrap();
+        assert_eq!(performance, performance_b)
+    }
+
+    #[test]
+    fn test_canonical() {
+        let performance_a = generate_performance(&[b"actor_a", b"actor_b", b"actor_c"]);
+        let performance_b = generate_performance(&[b"actor_c", b"actor_a", b"actor_b"]);
+        assert_eq!(
+            Bytes::from(performance_a.clone()),
+            Bytes::from(performance_b.clone())
+        );
+        assert_eq!(performance_a.get_id(), performance_b.get_id())
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let performance = generate_performance(&[b"actor_a"]);
+        let mut raw = Bytes::from(performance).to_vec();
+        raw.push(0);
+        assert!(Performance::try_from(Bytes::from(raw)).is_err())
+    }
+}
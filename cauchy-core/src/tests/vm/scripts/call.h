@@ -0,0 +1,123 @@
+#ifndef __CALL_H
+#define __CALL_H
+
+#include "vm.h"
+
+/*
+    Helpers for decoding calls passed as aux data. See
+    cauchy-core/src/primitives/call.rs for the host side encoding:
+
+    Selector (u32 LE) || Number of arguments (u8) || (Tag || Argument) ...
+*/
+
+#define CALL_ARG_U32 (0)
+#define CALL_ARG_U64 (1)
+#define CALL_ARG_BYTES (2)
+#define CALL_ARG_ID (3)
+
+#define CALL_ID_SZ (32)
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct
+{
+    const uint8_t *data;
+    uint32_t size;
+    uint32_t pos;
+    uint32_t selector;
+    uint8_t n_args;
+} __vm_call_t;
+
+static uint32_t __call_read_u32(const uint8_t *const src)
+{
+    return (uint32_t)src[0] | ((uint32_t)src[1] << 8) | ((uint32_t)src[2] << 16) | ((uint32_t)src[3] << 24);
+}
+
+// Copies the aux data into buffer and decodes the call header
+static bool __vm_call_init(__vm_call_t *const call, uint8_t *const buffer, uint32_t buffer_sz)
+{
+    uint32_t aux_sz = __vm_auxdata(buffer, 0, buffer_sz);
+    if (aux_sz > buffer_sz || aux_sz < 5)
+    {
+        return false;
+    }
+    call->data = buffer;
+    call->size = aux_sz;
+    call->selector = __call_read_u32(buffer);
+    call->n_args = buffer[4];
+    call->pos = 5;
+    return true;
+}
+
+// Checks the tag of the next argument and advances past it
+static bool __call_expect(__vm_call_t *const call, uint8_t tag, uint32_t size)
+{
+    if (call->size - call->pos < 1 || call->size - call->pos - 1 < size || call->data[call->pos] != tag)
+    {
+        return false;
+    }
+    call->pos += 1;
+    return true;
+}
+
+static bool __vm_call_u32(__vm_call_t *const call, uint32_t *const value)
+{
+    if (!__call_expect(call, CALL_ARG_U32, 4))
+    {
+        return false;
+    }
+    *value = __call_read_u32(call->data + call->pos);
+    call->pos += 4;
+    return true;
+}
+
+static bool __vm_call_u64(__vm_call_t *const call, uint64_t *const value)
+{
+    if (!__call_expect(call, CALL_ARG_U64, 8))
+    {
+        return false;
+    }
+    *value = (uint64_t)__call_read_u32(call->data + call->pos) | ((uint64_t)__call_read_u32(call->data + call->pos + 4) << 32);
+    call->pos += 8;
+    return true;
+}
+
+// Points data at the bytes argument in place
+static bool __vm_call_bytes(__vm_call_t *const call, const uint8_t **const data, uint32_t *const size)
+{
+    if (!__call_expect(call, CALL_ARG_BYTES, 4))
+    {
+        return false;
+    }
+    uint32_t len = __call_read_u32(call->data + call->pos);
+    // pos + 4 + len may wrap, compare against what remains instead
+    if (len > call->size - call->pos - 4)
+    {
+        call->pos -= 1;
+        return false;
+    }
+    *data = call->data + call->pos + 4;
+    *size = len;
+    call->pos += 4 + len;
+    return true;
+}
+
+// Points id at the CALL_ID_SZ byte actor id in place
+static bool __vm_call_id(__vm_call_t *const call, const uint8_t **const id)
+{
+    if (!__call_expect(call, CALL_ARG_ID, CALL_ID_SZ))
+    {
+        return false;
+    }
+    *id = call->data + call->pos;
+    call->pos += CALL_ID_SZ;
+    return true;
+}
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -134,6 +134,20 @@ void __vm_rand(void *const buffer, uint32_t size)
     );
 }
 
+void __vm_emit(const void *const topic, uint32_t topic_size, const void *const data, uint32_t data_size)
+{
+    __asm__ volatile(
+        "mv a3, %0\n\t"
+        "mv a4, %1\n\t"
+        "mv a5, %2\n\t"
+        "mv a6, %3\n\t"
+        "li a7, 0xCBF7\n\t"
+        "ecall\n\t"
+        : /* no outputs */
+        : "r"(topic), "r"(topic_size), "r"(data), "r"(data_size)
+        : "a3", "a4", "a5", "a6", "a7");
+}
+
 void __vm_exit(const int ret)
 {
     __asm__ volatile(
@@ -0,0 +1,80 @@
+// Decoding of calls passed as aux data, see cauchy-core/src/primitives/call.rs
+
+pub const ARG_U32: u8 = 0;
+pub const ARG_U64: u8 = 1;
+pub const ARG_BYTES: u8 = 2;
+pub const ARG_ID: u8 = 3;
+
+pub const ID_LEN: usize = 32;
+
+pub struct Call<'a> {
+    raw: &'a [u8],
+    pos: usize,
+    selector: u32,
+    n_args: u8,
+}
+
+fn read_u32(raw: &[u8]) -> u32 {
+    u32::from(raw[0])
+        | (u32::from(raw[1]) << 8)
+        | (u32::from(raw[2]) << 16)
+        | (u32::from(raw[3]) << 24)
+}
+
+impl<'a> Call<'a> {
+    pub fn new(raw: &'a [u8]) -> Option<Call<'a>> {
+        if raw.len() < 5 {
+            return None;
+        }
+        Some(Call {
+            raw,
+            pos: 5,
+            selector: read_u32(raw),
+            n_args: raw[4],
+        })
+    }
+
+    pub fn selector(&self) -> u32 {
+        self.selector
+    }
+
+    pub fn n_args(&self) -> u8 {
+        self.n_args
+    }
+
+    // Checks the tag of the next argument and returns its body
+    fn expect(&mut self, tag: u8, size: usize) -> Option<&'a [u8]> {
+        if self.raw.len() - self.pos < 1 + size || self.raw[self.pos] != tag {
+            return None;
+        }
+        let body = &self.raw[self.pos + 1..self.pos + 1 + size];
+        self.pos += 1 + size;
+        Some(body)
+    }
+
+    pub fn next_u32(&mut self) -> Option<u32> {
+        self.expect(ARG_U32, 4).map(read_u32)
+    }
+
+    pub fn next_u64(&mut self) -> Option<u64> {
+        self.expect(ARG_U64, 8)
+            .map(|raw| u64::from(read_u32(raw)) | (u64::from(read_u32(&raw[4..])) << 32))
+    }
+
+    pub fn next_bytes(&mut self) -> Option<&'a [u8]> {
+        if self.raw.len() - self.pos < 5 || self.raw[self.pos] != ARG_BYTES {
+            return None;
+        }
+        let len = read_u32(&self.raw[self.pos + 1..]) as usize;
+        if len > self.raw.len() - self.pos - 5 {
+            return None;
+        }
+        let body = &self.raw[self.pos + 5..self.pos + 5 + len];
+        self.pos += 5 + len;
+        Some(body)
+    }
+
+    pub fn next_id(&mut self) -> Option<&'a [u8]> {
+        self.expect(ARG_ID, ID_LEN)
+    }
+}
@@ -3,6 +3,8 @@
 #![feature(asm)]
 use core::panic::PanicInfo;
 
+mod call;
+
 /// This function is called on panic.
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
@@ -28,6 +30,26 @@ fn __vm_exit(code: u8) {
     }
 }
 
+// Returns the total number of bytes available in the aux data
+fn __vm_auxdata(buff: &mut [u8], index: usize) -> usize {
+    let buff_sz = buff.len();
+    let mut aux_sz: usize;
+
+    unsafe {
+        asm!("
+        mv a4, $1
+        mv a5, $2
+        mv a6, $3
+        li a7, 0xCBFB
+        ecall
+        mv $0, s2"
+        : "=r"(aux_sz)
+        : "r"(buff as *mut _ as *mut u8), "r"(index), "r"(buff_sz)
+        : "a4", "a5", "a6", "a7" );
+    }
+    aux_sz
+}
+
 fn __vm_send(txid : &[u8], msg: &[u8]) {
     let txid_sz = txid.len() as u64;
     let msg_sz = msg.len() as u64;
@@ -90,5 +112,13 @@ pub extern "C" fn _start()  {
      __vm_send(&sender_txid, &data);
      __vm_send(b"RECVR_RUST", b"DEADBEEF is happyBEEF, especially RUSTED beef!");
     // __vm_send(b"abcd", b"bazy");
-     __vm_exit(8);
+
+     // Exit with the first argument of a call passed as aux data
+     let mut aux : [u8;256] = [0; 256];
+     let aux_sz = __vm_auxdata(&mut aux, 0);
+     let amount = call::Call::new(&aux[..aux_sz.min(aux.len())]).and_then(|mut call| call.next_u64());
+     match amount {
+         Some(amount) => __vm_exit(amount as u8),
+         None => __vm_exit(8),
+     }
 }
\ No newline at end of file
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::Read;
+
+use bytes::Bytes;
+
+use crate::vm::validation::{check_timestamp, validate_binary, Timeliness};
+
+const MAX_BINARY_SIZE: usize = 1 << 20;
+const MAX_MEMORY: u64 = 4 << 20;
+
+fn load_script(path: &str) -> Vec<u8> {
+    let mut file = File::open(path).unwrap();
+    let mut script = Vec::new();
+    file.read_to_end(&mut script).unwrap();
+    script
+}
+
+#[test]
+fn test_valid() {
+    let script = load_script("src/tests/vm/scripts/sha256");
+    assert!(validate_binary(&Bytes::from(script), MAX_BINARY_SIZE, MAX_MEMORY).is_ok())
+}
+
+#[test]
+fn test_not_elf() {
+    let binary = Bytes::from(&b"binary"[..]);
+    assert!(validate_binary(&binary, MAX_BINARY_SIZE, MAX_MEMORY).is_err())
+}
+
+#[test]
+fn test_too_large() {
+    let script = load_script("src/tests/vm/scripts/sha256");
+    assert!(validate_binary(&Bytes::from(script), 1024, MAX_MEMORY).is_err())
+}
+
+#[test]
+fn test_wrong_architecture() {
+    let mut script = load_script("src/tests/vm/scripts/sha256");
+    // x86-64
+    script[18] = 62;
+    script[19] = 0;
+    assert!(validate_binary(&Bytes::from(script), MAX_BINARY_SIZE, MAX_MEMORY).is_err())
+}
+
+#[test]
+fn test_invalid_entry_point() {
+    let mut script = load_script("src/tests/vm/scripts/sha256");
+    for byte in script[24..32].iter_mut() {
+        *byte = 0xff;
+    }
+    assert!(validate_binary(&Bytes::from(script), MAX_BINARY_SIZE, MAX_MEMORY).is_err())
+}
+
+#[test]
+fn test_memory_limit() {
+    let script = load_script("src/tests/vm/scripts/sha256");
+    assert!(validate_binary(&Bytes::from(script), MAX_BINARY_SIZE, 0x1000).is_err())
+}
+
+const NOW: u64 = 1_000_000;
+const MAX_PAST: u64 = 60_000;
+const MAX_FUTURE: u64 = 10_000;
+
+#[test]
+fn test_timestamp_ready() {
+    assert_eq!(
+        check_timestamp(NOW, NOW, MAX_PAST, MAX_FUTURE).unwrap(),
+        Timeliness::Ready
+    );
+    assert_eq!(
+        check_timestamp(NOW - MAX_PAST, NOW, MAX_PAST, MAX_FUTURE).unwrap(),
+        Timeliness::Ready
+    );
+}
+
+#[test]
+fn test_timestamp_deferred() {
+    assert_eq!(
+        check_timestamp(NOW + MAX_FUTURE, NOW, MAX_PAST, MAX_FUTURE).unwrap(),
+        Timeliness::Deferred
+    );
+}
+
+#[test]
+fn test_timestamp_out_of_window() {
+    assert!(check_timestamp(NOW - MAX_PAST - 1, NOW, MAX_PAST, MAX_FUTURE).is_err());
+    assert!(check_timestamp(NOW + MAX_FUTURE + 1, NOW, MAX_PAST, MAX_FUTURE).is_err());
+}
@@ -3,6 +3,22 @@ pub const VALUE_LEN: usize = 256;
 pub const PUBKEY_LEN: usize = 33;
 pub const SIG_LEN: usize = 64;
 pub const SKETCH_CAPACITY: usize = 32; // TODO: This should become dynamic
+pub const MAX_PEERS_PER_MSG: usize = 64;
+pub const MAX_IDS_PER_MSG: usize = 1 << 16;
+pub const MAX_INVENTORY_PER_MSG: usize = 256; // Ids per inventory or relay request
+pub const MAX_FRAME_LEN: usize = 1 << 24;
+pub const MAX_TXS_PAYLOAD_LEN: usize = 1 << 23; // Encoded transactions per message
+pub const MAX_EVENT_TOPIC_LEN: u64 = 64;
+pub const MAX_EVENT_DATA_LEN: u64 = 1 << 12;
+pub const MAX_EVENTS_PER_ACT: usize = 256;
+pub const MAX_EVENTS_PER_FETCH: usize = 1 << 10; // Events returned per fetch request
+pub const EVENT_CHANNEL_CAPACITY: usize = 1 << 10; // Stage events buffered per subscriber
+pub const PROTOCOL_VERSION: u64 = 3;
+pub const MIN_PROTOCOL_VERSION: u64 = 1;
+pub const LOCAL_FEATURES: u64 = 0; // Bit flags of optional messages understood
+pub const PING_VERSION: u64 = 2; // Peers from this version answer pings
+pub const WORK_SKETCH_LEN_VERSION: u64 = 2; // Peers from this version prefix work sketches with their length
+pub const RELAY_VERSION: u64 = 3; // Peers from this version relay inventory
 
 use std::fs;
 use std::io::Read;
@@ -96,11 +112,233 @@ impl Default for Mining {
     }
 }
 
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MachineFlavour {
+    Sparse,
+    Flat,
+    Asm, // Only available on x86_64 linux with the asm feature
+}
+
+impl MachineFlavour {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            MachineFlavour::Sparse => "sparse",
+            MachineFlavour::Flat => "flat",
+            MachineFlavour::Asm => "asm",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct VirtualMachine {
+    pub max_binary_size: usize,
+    pub max_memory: u64,
+    pub machine: MachineFlavour,
+}
+
+impl Default for VirtualMachine {
+    fn default() -> Self {
+        VirtualMachine {
+            max_binary_size: 1 << 20,
+            max_memory: 4 << 20,
+            machine: MachineFlavour::Sparse,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Mempool {
+    pub size: usize,
+    #[serde(deserialize_with = "from_u64")]
+    pub drain_interval_ms: Duration,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool {
+            size: 1024,
+            drain_interval_ms: duration_from_millis(2_000),
+        }
+    }
+}
+
+// Acceptance window for new transaction timestamps
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Admission {
+    #[serde(deserialize_with = "from_u64")]
+    pub max_past_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub max_future_ms: Duration,
+}
+
+impl Default for Admission {
+    fn default() -> Self {
+        Admission {
+            max_past_ms: duration_from_millis(60_000),
+            max_future_ms: duration_from_millis(10_000),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Banning {
+    pub threshold: u32,
+    #[serde(deserialize_with = "from_u64")]
+    pub ban_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub decay_ms: Duration, // Time for a score to drop by one point
+}
+
+impl Default for Banning {
+    fn default() -> Self {
+        Banning {
+            threshold: 100,
+            ban_ms: duration_from_millis(86_400_000),
+            decay_ms: duration_from_millis(60_000),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Discovery {
+    pub max_addresses: usize,
+    pub max_failures: u32,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Discovery {
+            max_addresses: 1024,
+            max_failures: 10,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Connections {
+    pub target_outbound: usize,
+    pub max_inbound: usize,
+    #[serde(deserialize_with = "from_u64")]
+    pub dial_interval_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub connect_timeout_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub backoff_base_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub backoff_max_ms: Duration,
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        Connections {
+            target_outbound: 8,
+            max_inbound: 32,
+            dial_interval_ms: duration_from_millis(5_000),
+            connect_timeout_ms: duration_from_millis(10_000),
+            backoff_base_ms: duration_from_millis(5_000),
+            backoff_max_ms: duration_from_millis(600_000),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Keepalive {
+    #[serde(deserialize_with = "from_u64")]
+    pub ping_interval_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub ping_timeout_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub idle_timeout_ms: Duration, // Silence tolerated from peers too old to answer pings
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Keepalive {
+            ping_interval_ms: duration_from_millis(10_000),
+            ping_timeout_ms: duration_from_millis(20_000),
+            idle_timeout_ms: duration_from_millis(120_000),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Timeouts {
+    #[serde(deserialize_with = "from_u64")]
+    pub work_pull_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub state_pull_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub state_push_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub pulling_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub handshake_ms: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            work_pull_ms: duration_from_millis(5_000),
+            state_pull_ms: duration_from_millis(30_000),
+            state_push_ms: duration_from_millis(30_000),
+            pulling_ms: duration_from_millis(60_000),
+            handshake_ms: duration_from_millis(10_000),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "UPPERCASE", default)]
+pub struct Relay {
+    #[serde(deserialize_with = "from_u64")]
+    pub announce_interval_ms: Duration,
+    #[serde(deserialize_with = "from_u64")]
+    pub request_timeout_ms: Duration,
+    pub max_known: usize,
+}
+
+impl Default for Relay {
+    fn default() -> Self {
+        Relay {
+            announce_interval_ms: duration_from_millis(1_000),
+            request_timeout_ms: duration_from_millis(30_000),
+            max_known: 8_192,
+        }
+    }
+}
+
 #[derive(Deserialize, Default)]
 pub struct Config {
     pub network: Networking,
     pub mining: Mining,
     pub debugging: Debugging,
+    #[serde(default)]
+    pub vm: VirtualMachine,
+    #[serde(default)]
+    pub mempool: Mempool,
+    #[serde(default)]
+    pub admission: Admission,
+    #[serde(default)]
+    pub banning: Banning,
+    #[serde(default)]
+    pub discovery: Discovery,
+    #[serde(default)]
+    pub connections: Connections,
+    #[serde(default)]
+    pub keepalive: Keepalive,
+    #[serde(default)]
+    pub timeouts: Timeouts,
+    #[serde(default)]
+    pub relay: Relay,
 }
 
 lazy_static! {
@@ -9,6 +9,10 @@ pub struct InvalidSignature;
 #[fail(display = "invalid pubkey")]
 pub struct InvalidPubkey;
 
+#[derive(Debug, Fail)]
+#[fail(display = "invalid secret key")]
+pub struct InvalidSeckey;
+
 // Parsing Errors
 #[derive(Debug, Fail)]
 #[fail(display = "varint parsing ran out of bytes at {}", len)]
@@ -16,6 +20,12 @@ pub struct VarIntParseError {
     pub len: usize,
 }
 
+#[derive(Debug, Fail)]
+#[fail(display = "non-canonical encoding of {}", name)]
+pub struct NonCanonicalError {
+    pub name: &'static str,
+}
+
 // Decode errors
 #[derive(Debug, Fail)]
 pub enum CryptoDecodingError {
@@ -44,10 +54,77 @@ pub enum TransactionDeserialisationError {
     BinaryTooShort,
 }
 
+#[derive(Debug, Fail)]
+pub enum PerformanceDeserialisationError {
+    #[fail(display = "performance too short")]
+    TooShort,
+    #[fail(display = "trailing bytes after performance")]
+    TrailingBytes,
+}
+
+// Admission Errors
+#[derive(Debug, Fail)]
+pub enum BinaryValidationError {
+    #[fail(display = "binary too large: {} bytes", size)]
+    TooLarge { size: usize },
+    #[fail(display = "binary is not an elf")]
+    NotElf,
+    #[fail(display = "unsupported elf class or encoding")]
+    UnsupportedFormat,
+    #[fail(display = "unsupported architecture: {}", machine)]
+    WrongArchitecture { machine: u16 },
+    #[fail(display = "binary is not executable")]
+    NotExecutable,
+    #[fail(display = "malformed program headers")]
+    MalformedHeaders,
+    #[fail(display = "segment exceeds memory limit")]
+    SegmentTooLarge,
+    #[fail(display = "no loadable segments")]
+    NoLoadableSegments,
+    #[fail(display = "entry point {:#x} not in an executable segment", entry)]
+    InvalidEntryPoint { entry: u64 },
+}
+
+#[derive(Debug, Fail)]
+pub enum TimestampError {
+    #[fail(display = "timestamp {} too far before {}", time, now)]
+    TooOld { time: u64, now: u64 },
+    #[fail(display = "timestamp {} too far after {}", time, now)]
+    TooFuture { time: u64, now: u64 },
+}
+
+#[derive(Debug, Fail)]
+pub enum CallError {
+    #[fail(display = "too many arguments: {}", n)]
+    TooManyArguments { n: usize },
+    #[fail(display = "invalid id length: {}", len)]
+    InvalidIdLength { len: usize },
+}
+
+#[derive(Debug, Fail)]
+pub enum CallDeserialisationError {
+    #[fail(display = "call too short")]
+    TooShort,
+    #[fail(display = "trailing bytes after call")]
+    TrailingBytes,
+    #[fail(display = "unknown argument tag: {}", tag)]
+    UnknownTag { tag: u8 },
+}
+
 #[derive(Debug, Fail)]
 #[fail(display = "invalid peer list")]
 pub struct PeerDeserialisationError;
 
+#[derive(Debug, Fail)]
+pub enum AddressDeserialisationError {
+    #[fail(display = "address too short")]
+    TooShort,
+    #[fail(display = "trailing bytes after address")]
+    TrailingBytes,
+    #[fail(display = "unknown address version: {}", version)]
+    UnknownVersion { version: u8 },
+}
+
 #[derive(Debug, Fail)]
 #[fail(display = "invalid varint")]
 pub struct VarIntDeserialisationError;
@@ -106,6 +183,34 @@ pub enum DaemonError {
     Unreachable,
 }
 
+#[derive(Debug, Fail)]
+pub enum TransportError {
+    #[fail(display = "frame too large: {} bytes", len)]
+    FrameTooLarge { len: usize },
+    #[fail(display = "message {} too large: {} bytes", tag, len)]
+    MessageTooLarge { tag: u8, len: usize },
+    #[fail(display = "encryption failure")]
+    Encryption,
+    #[fail(display = "frame failed authentication")]
+    Decryption,
+    #[fail(display = "nonce exhausted")]
+    NonceExhausted,
+    #[fail(display = "reflected ephemeral key")]
+    ReflectedKey,
+    #[fail(display = "handshake timed out")]
+    HandshakeTimeout,
+}
+
+#[derive(Debug, Fail)]
+pub enum IdentityError {
+    #[fail(display = "identity key already exists at {}", path)]
+    AlreadyExists { path: String },
+    #[fail(display = "no identity key at {}", path)]
+    Missing { path: String },
+    #[fail(display = "no home directory")]
+    NoHomeDirectory,
+}
+
 #[derive(Debug, Fail)]
 pub enum RPCError {
     #[fail(display = "socket binding failure")]
@@ -0,0 +1,129 @@
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use failure::Error;
+use log::{info, warn};
+use secp256k1::key::{PublicKey, SecretKey};
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+
+use super::errors::IdentityError;
+use crate::crypto::signatures::ecdsa::*;
+
+// Node keypair, kept in the data directory so the public key survives restarts
+pub fn identity_path() -> Result<PathBuf, Error> {
+    let mut path = dirs::home_dir().ok_or(IdentityError::NoHomeDirectory)?;
+    path.push(".cauchy/identity.key");
+    Ok(path)
+}
+
+pub fn read_identity(path: &Path) -> Result<Option<(SecretKey, PublicKey)>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    #[cfg(unix)]
+    {
+        if fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+            warn!(target: "startup_event", "identity key {} is accessible by other users", path.display());
+        }
+    }
+
+    let mut raw = vec![];
+    fs::File::open(path)?.read_to_end(&mut raw)?;
+    let sk = seckey_from_bytes(Bytes::from(raw))?;
+    Ok(Some((sk, pubkey_from_seckey(&sk))))
+}
+
+// Written to a fresh temporary file readable only by the owner, returning its path
+fn write_tmp_identity(path: &Path, sk: &SecretKey) -> Result<PathBuf, Error> {
+    if let Some(dir) = path.parent() {
+        let mut builder = DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        builder.mode(0o700);
+        builder.create(dir)?;
+    }
+
+    // A stale file would keep its permissions, as the mode only applies on creation
+    let tmp_path = path.with_extension("tmp");
+    if let Err(err) = fs::remove_file(&tmp_path) {
+        if err.kind() != ErrorKind::NotFound {
+            return Err(err.into());
+        }
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp_path)?;
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(&bytes_from_seckey(sk))?;
+    file.sync_all()?;
+    Ok(tmp_path)
+}
+
+// Written to a temporary file, then moved into place
+pub fn write_identity(path: &Path, sk: &SecretKey) -> Result<(), Error> {
+    let tmp_path = write_tmp_identity(path, sk)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn generate_identity(path: &Path) -> Result<PublicKey, Error> {
+    if path.exists() {
+        return Err(IdentityError::AlreadyExists {
+            path: path.display().to_string(),
+        }
+        .into());
+    }
+    let (sk, pk) = generate_keypair();
+    write_identity(path, &sk)?;
+    info!(target: "startup_event", "generated identity key {}", path.display());
+    Ok(pk)
+}
+
+// The previous key is kept alongside with an .old extension, which is never overwritten
+pub fn rotate_identity(path: &Path) -> Result<PublicKey, Error> {
+    if !path.exists() {
+        return Err(IdentityError::Missing {
+            path: path.display().to_string(),
+        }
+        .into());
+    }
+    let old_path = path.with_extension("old");
+    if old_path.exists() {
+        return Err(IdentityError::AlreadyExists {
+            path: old_path.display().to_string(),
+        }
+        .into());
+    }
+
+    // The current key stays in place until the new one is written
+    let (sk, pk) = generate_keypair();
+    let tmp_path = write_tmp_identity(path, &sk)?;
+    fs::hard_link(path, &old_path)?;
+    fs::rename(&tmp_path, path)?;
+    info!(target: "startup_event", "rotated identity key {}", path.display());
+    Ok(pk)
+}
+
+// Generates a keypair on first start
+pub fn load_identity(path: &Path) -> Result<(SecretKey, PublicKey), Error> {
+    match read_identity(path)? {
+        Some(keypair) => Ok(keypair),
+        None => {
+            generate_identity(path)?;
+            read_identity(path)?.ok_or_else(|| {
+                IdentityError::Missing {
+                    path: path.display().to_string(),
+                }
+                .into()
+            })
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod byte_ops;
 pub mod constants;
 pub mod errors;
+pub mod identity;
 pub mod logging;
 pub mod mining;
 pub mod parsing;
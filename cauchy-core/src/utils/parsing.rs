@@ -2,14 +2,29 @@ use bytes::{Buf, Bytes};
 use failure::Error;
 use log::info;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
 
 use crate::{
     crypto::sketches::{dummy_sketch::*, *},
-    primitives::{transaction::*, varint::*},
+    net::peers::*,
+    primitives::{
+        access_pattern::AccessPattern,
+        act::{Act, Event, Message},
+        call::*,
+        transaction::*,
+        varint::*,
+    },
+    vm::performance::Performance,
 };
 
-use super::{constants::*, errors::VarIntParseError};
+use super::{
+    constants::*,
+    errors::{
+        AddressDeserialisationError, CallDeserialisationError, NonCanonicalError,
+        PeerDeserialisationError, VarIntParseError,
+    },
+};
 
 pub trait Parsable<U> {
     fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(U, usize)>, Error>;
@@ -89,7 +104,7 @@ impl Parsable<DummySketch> for DummySketch {
             None => return Ok(None),
         };
         let us_pos_len = usize::from(vi_pos_len);
-        let mut pos_set = HashSet::with_capacity(us_pos_len);
+        let mut pos_set = HashSet::with_capacity(us_pos_len.min(buf.remaining() / HASH_LEN));
         for i in 0..us_pos_len {
             info!(target: "parsing_event", "ID {} of {}", i, us_pos_len);
             if buf.remaining() < HASH_LEN {
@@ -106,3 +121,326 @@ impl Parsable<DummySketch> for DummySketch {
         )))
     }
 }
+
+// Parse a VarInt length prefix followed by that many bytes
+fn parse_var_bytes<T: Buf>(buf: &mut T) -> Result<Option<(Bytes, usize)>, Error> {
+    let (vi_len, vi_len_len) = match VarInt::parse_buf(buf)? {
+        Some(some) => some,
+        None => return Ok(None),
+    };
+    let us_len = usize::from(vi_len);
+    if buf.remaining() < us_len {
+        return Ok(None);
+    }
+    let mut dst = vec![0; us_len];
+    buf.copy_to_slice(&mut dst);
+    Ok(Some((Bytes::from(dst), vi_len_len + us_len)))
+}
+
+impl Parsable<AccessPattern> for AccessPattern {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(AccessPattern, usize)>, Error> {
+        info!(target: "parsing_event", "begin access pattern parsing");
+        let (vi_n_reads, mut total_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        let us_n_reads = usize::from(vi_n_reads);
+        let mut read = HashSet::new();
+        let mut last_key: Option<Bytes> = None;
+        for _ in 0..us_n_reads {
+            let (key, key_len) = match parse_var_bytes(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            // Keys must be strictly increasing
+            if let Some(last_key) = last_key {
+                if last_key >= key {
+                    return Err(NonCanonicalError {
+                        name: "access pattern reads",
+                    }
+                    .into());
+                }
+            }
+            read.insert(key.clone());
+            last_key = Some(key);
+            total_len += key_len;
+        }
+
+        let (vi_n_writes, vi_n_writes_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        total_len += vi_n_writes_len;
+        let us_n_writes = usize::from(vi_n_writes);
+        let mut write = HashMap::new();
+        let mut last_key: Option<Bytes> = None;
+        for _ in 0..us_n_writes {
+            let (key, key_len) = match parse_var_bytes(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            let (value, value_len) = match parse_var_bytes(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            if let Some(last_key) = last_key {
+                if last_key >= key {
+                    return Err(NonCanonicalError {
+                        name: "access pattern writes",
+                    }
+                    .into());
+                }
+            }
+            write.insert(key.clone(), value);
+            last_key = Some(key);
+            total_len += key_len + value_len;
+        }
+        info!(target: "parsing_event", "finished access pattern parsing");
+        Ok(Some((AccessPattern { read, write }, total_len)))
+    }
+}
+
+impl Parsable<Message> for Message {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Message, usize)>, Error> {
+        let (sender, sender_len) = match parse_var_bytes(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        let (receiver, receiver_len) = match parse_var_bytes(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        let (payload, payload_len) = match parse_var_bytes(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        Ok(Some((
+            Message::new(sender, receiver, payload),
+            sender_len + receiver_len + payload_len,
+        )))
+    }
+}
+
+impl Parsable<Event> for Event {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Event, usize)>, Error> {
+        let (topic, topic_len) = match parse_var_bytes(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        let (data, data_len) = match parse_var_bytes(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        Ok(Some((Event::new(topic, data), topic_len + data_len)))
+    }
+}
+
+impl Parsable<Act> for Act {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Act, usize)>, Error> {
+        info!(target: "parsing_event", "begin act parsing");
+        let (access_pattern, mut total_len) = match AccessPattern::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+
+        let (vi_n_msgs, vi_n_msgs_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        total_len += vi_n_msgs_len;
+        let us_n_msgs = usize::from(vi_n_msgs);
+        let mut messages = vec![];
+        for _ in 0..us_n_msgs {
+            let (message, message_len) = match Message::parse_buf(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            messages.push(message);
+            total_len += message_len;
+        }
+
+        let (vi_n_events, vi_n_events_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        total_len += vi_n_events_len;
+        let us_n_events = usize::from(vi_n_events);
+        let mut events = vec![];
+        for _ in 0..us_n_events {
+            let (event, event_len) = match Event::parse_buf(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            events.push(event);
+            total_len += event_len;
+        }
+
+        let (vi_operations, vi_operations_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        total_len += vi_operations_len;
+        info!(target: "parsing_event", "finished act parsing");
+        Ok(Some((
+            Act::new(access_pattern, messages, events, u64::from(vi_operations)),
+            total_len,
+        )))
+    }
+}
+
+impl Parsable<Performance> for Performance {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Performance, usize)>, Error> {
+        info!(target: "parsing_event", "begin performance parsing");
+        let (vi_n_acts, mut total_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        let us_n_acts = usize::from(vi_n_acts);
+        let mut performance = Performance::default();
+        let mut last_id: Option<Bytes> = None;
+        for _ in 0..us_n_acts {
+            let (id, id_len) = match parse_var_bytes(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            // Actor IDs must be strictly increasing
+            if let Some(last_id) = last_id {
+                if last_id >= id {
+                    return Err(NonCanonicalError {
+                        name: "performance actor ids",
+                    }
+                    .into());
+                }
+            }
+            let (act, act_len) = match Act::parse_buf(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            performance.0.insert(id.clone(), act);
+            last_id = Some(id);
+            total_len += id_len + act_len;
+        }
+        info!(target: "parsing_event", "finished performance parsing");
+        Ok(Some((performance, total_len)))
+    }
+}
+
+impl Parsable<Peer> for Peer {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Peer, usize)>, Error> {
+        if buf.remaining() < 1 {
+            return Ok(None);
+        }
+        let version = buf.get_u8();
+        let ip = match version {
+            ADDR_V4 => {
+                if buf.remaining() < 4 + 2 {
+                    return Ok(None);
+                }
+                let mut dst_ip = [0; 4];
+                buf.copy_to_slice(&mut dst_ip);
+                IpAddr::from(dst_ip)
+            }
+            ADDR_V6 => {
+                if buf.remaining() < 16 + 2 {
+                    return Ok(None);
+                }
+                let mut dst_ip = [0; 16];
+                buf.copy_to_slice(&mut dst_ip);
+                IpAddr::from(dst_ip)
+            }
+            _ => return Err(AddressDeserialisationError::UnknownVersion { version }.into()),
+        };
+        let port = buf.get_u16_be();
+        let len = match ip {
+            IpAddr::V4(_) => 1 + 4 + 2,
+            IpAddr::V6(_) => 1 + 16 + 2,
+        };
+        Ok(Some((Peer::new(SocketAddr::new(ip, port)), len)))
+    }
+}
+
+impl Parsable<Peers> for Peers {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Peers, usize)>, Error> {
+        let (vi_n, mut total_len) = match VarInt::parse_buf(buf)? {
+            Some(some) => some,
+            None => return Ok(None),
+        };
+        let n = usize::from(vi_n);
+        if n > MAX_PEERS_PER_MSG {
+            return Err(PeerDeserialisationError.into());
+        }
+
+        let mut vec_peers = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (peer, peer_len) = match Peer::parse_buf(buf)? {
+                Some(some) => some,
+                None => return Ok(None),
+            };
+            vec_peers.push(peer);
+            total_len += peer_len;
+        }
+        Ok(Some((Peers::new(vec_peers), total_len)))
+    }
+}
+
+impl Parsable<Call> for Call {
+    fn parse_buf<T: Buf>(buf: &mut T) -> Result<Option<(Call, usize)>, Error> {
+        info!(target: "parsing_event", "begin call parsing");
+        if buf.remaining() < 5 {
+            return Ok(None);
+        }
+        let selector = buf.get_u32_le();
+        let n_args = usize::from(buf.get_u8());
+        let mut total_len = 5;
+        let mut arguments = Vec::with_capacity(n_args);
+        for _ in 0..n_args {
+            if buf.remaining() < 1 {
+                return Ok(None);
+            }
+            let tag = buf.get_u8();
+            total_len += 1;
+            let argument = match tag {
+                ARG_U32 => {
+                    if buf.remaining() < 4 {
+                        return Ok(None);
+                    }
+                    total_len += 4;
+                    Argument::U32(buf.get_u32_le())
+                }
+                ARG_U64 => {
+                    if buf.remaining() < 8 {
+                        return Ok(None);
+                    }
+                    total_len += 8;
+                    Argument::U64(buf.get_u64_le())
+                }
+                ARG_BYTES => {
+                    if buf.remaining() < 4 {
+                        return Ok(None);
+                    }
+                    let us_len = buf.get_u32_le() as usize;
+                    if buf.remaining() < us_len {
+                        return Ok(None);
+                    }
+                    let mut dst = vec![0; us_len];
+                    buf.copy_to_slice(&mut dst);
+                    total_len += 4 + us_len;
+                    Argument::Bytes(Bytes::from(dst))
+                }
+                ARG_ID => {
+                    if buf.remaining() < HASH_LEN {
+                        return Ok(None);
+                    }
+                    let mut dst = vec![0; HASH_LEN];
+                    buf.copy_to_slice(&mut dst);
+                    total_len += HASH_LEN;
+                    Argument::Id(Bytes::from(dst))
+                }
+                _ => return Err(CallDeserialisationError::UnknownTag { tag }.into()),
+            };
+            arguments.push(argument);
+        }
+        info!(target: "parsing_event", "finished call parsing");
+        Ok(Some((Call::from_selector(selector, arguments)?, total_len)))
+    }
+}
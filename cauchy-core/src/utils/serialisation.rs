@@ -1,21 +1,28 @@
-use std::{
-    convert::TryFrom,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
-};
+use std::{convert::TryFrom, net::IpAddr};
 
 use bytes::{Buf, BufMut, Bytes, BytesMut, IntoBuf};
 use failure::Error;
 
 use crate::{
     crypto::{signatures::ecdsa::*, sketches::dummy_sketch::*},
-    net::peers::{Peer, Peers},
-    primitives::{access_pattern::*, transaction::Transaction, varint::VarInt, work::WorkSite},
+    net::peers::*,
+    primitives::{
+        access_pattern::*,
+        act::{Act, Event, Message},
+        call::{Argument, Call},
+        transaction::Transaction,
+        varint::VarInt,
+        work::WorkSite,
+    },
+    vm::performance::Performance,
 };
 
 use super::{
     constants::*,
     errors::{
-        PeerDeserialisationError, TransactionDeserialisationError, VarIntDeserialisationError,
+        AddressDeserialisationError, CallDeserialisationError, PeerDeserialisationError,
+        PerformanceDeserialisationError, TransactionDeserialisationError,
+        VarIntDeserialisationError,
     },
     parsing::*,
 };
@@ -148,57 +155,193 @@ impl From<DummySketch> for Bytes {
     }
 }
 
+fn extend_var_bytes(buf: &mut BytesMut, raw: &Bytes) {
+    buf.extend(Bytes::from(VarInt::from(raw.len())));
+    buf.extend_from_slice(&raw[..]);
+}
+
+/*
+    VarInt    ||    Keys    ||    VarInt    ||    (Key || Value) ...
+      ^ Number of reads             ^ Number of writes
+
+    Keys and values are each prefixed by their VarInt length and sorted
+    lexicographically so that equal access patterns serialise identically.
+*/
 impl From<AccessPattern> for Bytes {
     fn from(access_pattern: AccessPattern) -> Bytes {
-        let vi_read_len = VarInt::new(access_pattern.read.len() as u64);
-        let mut raw = BytesMut::with_capacity(
-            vi_read_len.len()
-                + access_pattern.read.len() * HASH_LEN
-                + access_pattern.write.len() * (HASH_LEN + VALUE_LEN),
-        );
+        let mut raw = BytesMut::new();
+
+        // Put reads
+        let mut reads: Vec<&Bytes> = access_pattern.read.iter().collect();
+        reads.sort();
+        raw.extend(Bytes::from(VarInt::from(reads.len())));
+        for key in reads {
+            extend_var_bytes(&mut raw, key);
+        }
+
+        // Put writes
+        let mut writes: Vec<(&Bytes, &Bytes)> = access_pattern.write.iter().collect();
+        writes.sort();
+        raw.extend(Bytes::from(VarInt::from(writes.len())));
+        for (key, value) in writes {
+            extend_var_bytes(&mut raw, key);
+            extend_var_bytes(&mut raw, value);
+        }
+        raw.freeze()
+    }
+}
 
-        // Put num of reads
-        raw.put(Bytes::from(vi_read_len));
+impl From<Message> for Bytes {
+    fn from(message: Message) -> Bytes {
+        let mut raw = BytesMut::new();
+        extend_var_bytes(&mut raw, &message.get_sender());
+        extend_var_bytes(&mut raw, &message.get_receiver());
+        extend_var_bytes(&mut raw, &message.get_payload());
+        raw.freeze()
+    }
+}
 
-        // Put read keys
-        for key in access_pattern.read.iter() {
-            raw.put(key);
+impl From<Event> for Bytes {
+    fn from(event: Event) -> Bytes {
+        let mut raw = BytesMut::new();
+        extend_var_bytes(&mut raw, &event.get_topic());
+        extend_var_bytes(&mut raw, &event.get_data());
+        raw.freeze()
+    }
+}
+
+/*
+    AccessPattern    ||    VarInt    ||    Messages    ||    VarInt    ||    Events    ||    VarInt
+                             ^ Number of messages              ^ Number of events           ^ Operations
+*/
+impl From<Act> for Bytes {
+    fn from(act: Act) -> Bytes {
+        let mut raw = BytesMut::new();
+        raw.extend(Bytes::from(act.access_pattern.clone()));
+
+        // Messages are kept in the order they were sent
+        let messages = act.get_messages();
+        raw.extend(Bytes::from(VarInt::from(messages.len())));
+        for message in messages {
+            raw.extend(Bytes::from(message));
         }
 
-        // Put writes
-        for (key, value) in access_pattern.write.iter() {
-            raw.put(key);
-            raw.put(value);
+        // Events are kept in the order they were emitted
+        let events = act.get_events();
+        raw.extend(Bytes::from(VarInt::from(events.len())));
+        for event in events {
+            raw.extend(Bytes::from(event));
         }
+
+        raw.extend(Bytes::from(VarInt::from(act.get_operations())));
         raw.freeze()
     }
 }
 
+/*
+    VarInt    ||    (Actor ID || Act) ...
+      ^ Number of acts, sorted by actor ID
+*/
+impl From<Performance> for Bytes {
+    fn from(performance: Performance) -> Bytes {
+        let mut acts: Vec<(Bytes, Act)> = performance.0.into_iter().collect();
+        acts.sort_by(|(id_a, _), (id_b, _)| id_a.cmp(id_b));
+
+        let mut raw = BytesMut::new();
+        raw.extend(Bytes::from(VarInt::from(acts.len())));
+        for (id, act) in acts {
+            extend_var_bytes(&mut raw, &id);
+            raw.extend(Bytes::from(act));
+        }
+        raw.freeze()
+    }
+}
+
+impl TryFrom<Bytes> for Performance {
+    type Error = Error;
+    fn try_from(raw: Bytes) -> Result<Performance, Self::Error> {
+        let mut buf = raw.into_buf();
+        match Performance::parse_buf(&mut buf)? {
+            Some((performance, _)) => {
+                if buf.remaining() != 0 {
+                    return Err(PerformanceDeserialisationError::TrailingBytes.into());
+                }
+                Ok(performance)
+            }
+            None => Err(PerformanceDeserialisationError::TooShort.into()),
+        }
+    }
+}
+
+impl From<Call> for Bytes {
+    fn from(call: Call) -> Bytes {
+        let mut raw = BytesMut::new();
+        raw.extend_from_slice(&call.get_selector().to_le_bytes());
+        raw.extend_from_slice(&[call.get_arguments().len() as u8]);
+        for argument in call.get_arguments() {
+            raw.extend_from_slice(&[argument.get_tag()]);
+            match argument {
+                Argument::U32(n) => raw.extend_from_slice(&n.to_le_bytes()),
+                Argument::U64(n) => raw.extend_from_slice(&n.to_le_bytes()),
+                Argument::Bytes(data) => {
+                    raw.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    raw.extend_from_slice(&data[..]);
+                }
+                Argument::Id(id) => raw.extend_from_slice(&id[..]),
+            }
+        }
+        raw.freeze()
+    }
+}
+
+impl TryFrom<Bytes> for Call {
+    type Error = Error;
+    fn try_from(raw: Bytes) -> Result<Call, Self::Error> {
+        let mut buf = raw.into_buf();
+        match Call::parse_buf(&mut buf)? {
+            Some((call, _)) => {
+                if buf.remaining() != 0 {
+                    return Err(CallDeserialisationError::TrailingBytes.into());
+                }
+                Ok(call)
+            }
+            None => Err(CallDeserialisationError::TooShort.into()),
+        }
+    }
+}
+
 impl From<Peer> for Bytes {
     fn from(peer: Peer) -> Bytes {
         let addr = peer.get_addr();
-        let ip = match addr.ip() {
-            IpAddr::V4(some) => some.octets(),
-            _ => unreachable!(), // TODO: IPV6
+        let mut raw = BytesMut::with_capacity(1 + 16 + 2);
+        match addr.ip() {
+            IpAddr::V4(ip) => {
+                raw.put_u8(ADDR_V4);
+                raw.put(&ip.octets()[..]);
+            }
+            IpAddr::V6(ip) => {
+                raw.put_u8(ADDR_V6);
+                raw.put(&ip.octets()[..]);
+            }
         };
-        let port = addr.port();
-        let mut raw = BytesMut::with_capacity(6);
-        raw.put(&ip[..]);
-        raw.put_u16_be(port);
+        raw.put_u16_be(addr.port());
         raw.freeze()
     }
 }
 
-impl From<Bytes> for Peer {
-    fn from(raw: Bytes) -> Peer {
+impl TryFrom<Bytes> for Peer {
+    type Error = Error;
+    fn try_from(raw: Bytes) -> Result<Peer, Error> {
         let mut buf = raw.into_buf();
-
-        let mut dst_ip = [0; 4];
-        buf.copy_to_slice(&mut dst_ip);
-
-        let port = buf.get_u16_be();
-
-        Peer::new(SocketAddr::from((dst_ip, port)))
+        match Peer::parse_buf(&mut buf)? {
+            Some((peer, _)) => {
+                if buf.remaining() != 0 {
+                    return Err(AddressDeserialisationError::TrailingBytes.into());
+                }
+                Ok(peer)
+            }
+            None => Err(AddressDeserialisationError::TooShort.into()),
+        }
     }
 }
 
@@ -206,25 +349,15 @@ impl TryFrom<Bytes> for Peers {
     type Error = Error;
     fn try_from(raw: Bytes) -> Result<Peers, Error> {
         let mut buf = raw.into_buf();
-        let (vi_n, _) = match VarInt::parse_buf(&mut buf) {
-            Ok(None) => return Err(PeerDeserialisationError.into()),
-            Err(err) => return Err(PeerDeserialisationError.into()),
-            Ok(Some(some)) => some,
-        };
-
-        let n = usize::from(vi_n);
-        if buf.remaining() < 6 * n {
-            return Err(PeerDeserialisationError.into());
-        }
-
-        let mut vec_peers = vec![];
-        for i in 0..n {
-            let mut dst = vec![0; 6];
-            buf.copy_to_slice(&mut dst);
-            vec_peers.push(Peer::from(Bytes::from(&dst[..])));
+        match Peers::parse_buf(&mut buf)? {
+            Some((peers, _)) => {
+                if buf.remaining() != 0 {
+                    return Err(PeerDeserialisationError.into());
+                }
+                Ok(peers)
+            }
+            None => Err(PeerDeserialisationError.into()),
         }
-
-        Ok(Peers::new(vec_peers))
     }
 }
 
@@ -232,7 +365,7 @@ impl From<Peers> for Bytes {
     fn from(peers: Peers) -> Bytes {
         let vec_peers = peers.into_vec();
         let usize_n = vec_peers.len();
-        let mut buf = BytesMut::with_capacity(6 * usize_n);
+        let mut buf = BytesMut::with_capacity(9 + 19 * usize_n);
 
         let vi_n = VarInt::from(usize_n as u64);
         buf.put(&Bytes::from(vi_n));
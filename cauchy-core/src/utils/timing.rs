@@ -5,7 +5,7 @@ pub fn get_current_time() -> u64 {
     let since_the_epoch = start
         .duration_since(UNIX_EPOCH)
         .expect("time went backwards");
-    since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_millis()) / 1_000_000
+    since_the_epoch.as_secs() * 1000 + u64::from(since_the_epoch.subsec_millis())
 }
 
 pub fn duration_from_millis(milli_secs: u64) -> Duration {
@@ -1,5 +1,6 @@
 pub mod performance;
 pub mod session;
+pub mod validation;
 
 use bytes::*;
 use std::sync::{Arc, Mutex};
@@ -18,23 +19,41 @@ use crate::{
     crypto::hashes::Identifiable,
     db::{mongodb::MongoDB, storing::*, *},
     primitives::{
-        act::{Act, Message},
+        act::{Act, Event, Message},
         transaction::Transaction,
     },
+    utils::constants::{MachineFlavour, CONFIG, MAX_EVENT_DATA_LEN, MAX_EVENT_TOPIC_LEN},
 };
+#[cfg(all(feature = "asm", target_arch = "x86_64", target_os = "linux"))]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
 use ckb_vm::{
-    CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, Memory, Register, SparseMemory,
-    SupportMachine, Syscalls, A0, A1, A2, A3, A4, A5, A6, A7, S1, S2,
+    CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, FlatMemory, Memory, Register,
+    SparseMemory, SupportMachine, Syscalls, A0, A1, A2, A3, A4, A5, A6, A7, S1, S2,
 };
 use std::io::{Read, Write};
 
+// Whether the asm machine was compiled in, otherwise it falls back to sparse
+pub const ASM_AVAILABLE: bool = cfg!(all(
+    feature = "asm",
+    target_arch = "x86_64",
+    target_os = "linux"
+));
+
 pub struct VM {
     store: MongoDB,
+    flavour: MachineFlavour,
 }
 
 impl VM {
     pub fn new(store: MongoDB) -> VM {
-        VM { store }
+        VM {
+            store,
+            flavour: CONFIG.vm.machine,
+        }
+    }
+
+    pub fn with_flavour(store: MongoDB, flavour: MachineFlavour) -> VM {
+        VM { store, flavour }
     }
 
     pub fn run(
@@ -57,19 +76,16 @@ impl VM {
             performance,
             child_branch: None,
             store: self.store.clone(),
+            events: 0,
         };
-        // Init machine
-        let mut machine =
-            DefaultMachineBuilder::<DefaultCoreMachine<u64, SparseMemory<u64>>>::default()
-                .syscall(Box::new(session))
-                .build();
 
         // Execute binary
-        machine = machine
-            .load_program(&tx.get_binary(), &vec![b"syscall".to_vec()])
-            .unwrap();
-        let result = machine.interpret();
-        drop(machine);
+        let args = vec![b"syscall".to_vec()];
+        let result = match self.flavour {
+            MachineFlavour::Sparse => execute_sparse(session, &tx.get_binary(), &args),
+            MachineFlavour::Flat => execute_flat(session, &tx.get_binary(), &args),
+            MachineFlavour::Asm => execute_asm(session, &tx.get_binary(), &args),
+        };
         info!(target: "vm_event", "execution completed");
 
         // Send termination alert to parent
@@ -80,6 +96,46 @@ impl VM {
     }
 }
 
+fn execute_sparse(session: Session, binary: &Bytes, args: &[Vec<u8>]) -> Result<u8, Error> {
+    let machine = DefaultMachineBuilder::<DefaultCoreMachine<u64, SparseMemory<u64>>>::default()
+        .syscall(Box::new(session))
+        .build();
+    let mut machine = machine.load_program(binary, args)?;
+    machine.interpret()
+}
+
+fn execute_flat(session: Session, binary: &Bytes, args: &[Vec<u8>]) -> Result<u8, Error> {
+    let machine = DefaultMachineBuilder::<DefaultCoreMachine<u64, FlatMemory<u64>>>::default()
+        .syscall(Box::new(session))
+        .build();
+    let mut machine = machine.load_program(binary, args)?;
+    machine.interpret()
+}
+
+#[cfg(all(feature = "asm", target_arch = "x86_64", target_os = "linux"))]
+fn execute_asm(session: Session, binary: &Bytes, args: &[Vec<u8>]) -> Result<u8, Error> {
+    let core = AsmCoreMachine::new_with_max_cycles(u64::max_value());
+    let machine = DefaultMachineBuilder::<Box<AsmCoreMachine>>::new(core)
+        .syscall(Box::new(session))
+        .build();
+    let mut machine = AsmMachine::new(machine);
+    machine.load_program(binary, args)?;
+    machine.run()
+}
+
+#[cfg(not(all(feature = "asm", target_arch = "x86_64", target_os = "linux")))]
+fn execute_asm(session: Session, binary: &Bytes, args: &[Vec<u8>]) -> Result<u8, Error> {
+    execute_sparse(session, binary, args)
+}
+
+// End of a script supplied buffer, rejecting oversized or wrapping ranges
+fn checked_end(addr: u64, size: u64, max_size: u64) -> Result<u64, Error> {
+    if size > max_size {
+        return Err(Error::OutOfBound);
+    }
+    addr.checked_add(size).ok_or(Error::OutOfBound)
+}
+
 pub struct Mailbox {
     inbox: Receiver<Message>,
     outbox: Sender<(Message, oneshot::Sender<()>)>,
@@ -297,12 +353,9 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for Session {
                 let index = machine.registers()[A5].to_usize();
                 let size = machine.registers()[A6].to_usize();
 
-                // Cap size at length of auxdata
-                let size = if size > self.aux.len() {
-                    self.aux.len()
-                } else {
-                    size
-                };
+                // Cap index and size at length of auxdata
+                let index = std::cmp::min(index, self.aux.len());
+                let size = std::cmp::min(size, self.aux.len() - index);
 
                 // TODO: Limit to buffer_sz
                 machine
@@ -317,10 +370,14 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for Session {
             0xCBFA => {
                 let txid_sz = machine.registers()[A5].to_usize();
                 let data_sz = machine.registers()[A6].to_usize();
+
+                // Cap sizes at length of auxdata
+                let txid_sz = std::cmp::min(txid_sz, self.aux.len());
+                let data_sz = std::cmp::min(data_sz, self.aux.len() - txid_sz);
                 let msg = Message::new(
                     self.id.clone(),
-                    Bytes::from(&self.aux[..txid_sz]),
-                    Bytes::from(&self.aux[txid_sz + 1..txid_sz + data_sz]),
+                    self.aux.slice(0, txid_sz),
+                    self.aux.slice(txid_sz, txid_sz + data_sz),
                 );
                 self.send(msg);
                 Ok(true)
@@ -341,6 +398,43 @@ impl<'a, Mac: SupportMachine> Syscalls<Mac> for Session {
                 self.exit();
                 Ok(true)
             }
+            // __vm_emit(topic, topic_sz, data, data_sz)
+            0xCBF7 => {
+                let topic_addr = machine.registers()[A3].to_u64();
+                let topic_sz = machine.registers()[A4].to_u64();
+                let data_addr = machine.registers()[A5].to_u64();
+                let data_sz = machine.registers()[A6].to_u64();
+                let topic_end = checked_end(topic_addr, topic_sz, MAX_EVENT_TOPIC_LEN)?;
+                let data_end = checked_end(data_addr, data_sz, MAX_EVENT_DATA_LEN)?;
+
+                // Load topic
+                let mut topic_bytes = Vec::<u8>::new();
+                for idx in topic_addr..topic_end {
+                    topic_bytes.push(
+                        machine
+                            .memory_mut()
+                            .load8(&Mac::REG::from_u64(idx))?
+                            .to_u8(),
+                    );
+                }
+
+                // Load data
+                let mut data_bytes = Vec::<u8>::new();
+                for idx in data_addr..data_end {
+                    data_bytes.push(
+                        machine
+                            .memory_mut()
+                            .load8(&Mac::REG::from_u64(idx))?
+                            .to_u8(),
+                    );
+                }
+
+                let event = Event::new(Bytes::from(topic_bytes), Bytes::from(data_bytes));
+                if !self.emit(event) {
+                    return Err(Error::OutOfBound);
+                }
+                Ok(true)
+            }
             _ => Ok(false),
         }
     }
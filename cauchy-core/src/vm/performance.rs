@@ -1,7 +1,10 @@
 #[macro_use(bson, doc)]
 use std::collections::HashMap;
 use std::ops::{Add, AddAssign};
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 
 use bson::{spec::BinarySubtype, *};
 use bytes::Bytes;
@@ -10,15 +13,15 @@ use futures::sink::Sink;
 use futures::sync::mpsc::{Receiver, Sender};
 use futures::sync::{mpsc, oneshot};
 use futures::{Future, Stream};
-use log::info;
+use log::{info, warn};
 use stream_cancel::{StreamExt, Tripwire};
 use tokio_threadpool::ThreadPool;
 
 use crate::{
-    crypto::hashes::Identifiable,
+    crypto::hashes::{blake2b::Blk2bHashable, Identifiable},
     db::{mongodb::MongoDB, storing::Storable, DataType, Database},
     primitives::{
-        act::{Act, Message},
+        act::{Act, Event, Message},
         transaction::Transaction,
     },
 };
@@ -27,7 +30,7 @@ use super::{Mailbox, VM};
 
 /* TODO: Given that each actor will write to one key
 this probably best as some sort of concurrent hashmap */
-#[derive(Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Performance(pub HashMap<Bytes, Act>); // Actor ID: Total Act
 
 impl Performance {
@@ -40,6 +43,12 @@ impl Performance {
     }
 }
 
+impl Identifiable for Performance {
+    fn get_id(&self) -> Bytes {
+        self.blake2b().blake2b()
+    }
+}
+
 impl AddAssign for Performance {
     fn add_assign(&mut self, other: Performance) {
         for (key, act) in other.0 {
@@ -78,6 +87,17 @@ impl Performance {
         act.access_pattern.write.insert(key, value);
     }
 
+    pub fn add_event(&mut self, id: &Bytes, event: Event) {
+        let act = match self.0.get_mut(id) {
+            Some(some) => some,
+            None => {
+                self.0.insert(id.clone(), Default::default());
+                self.0.get_mut(id).unwrap()
+            }
+        };
+        act.add_event(event);
+    }
+
     pub fn from_tx(
         db: MongoDB,
         tx: Transaction,
@@ -109,14 +129,20 @@ impl Performance {
 
         let pool = ThreadPool::new();
 
+        // Set if any VM fails to load or execute
+        let failed = Arc::new(AtomicBool::new(false));
+        let failed_outer = failed.clone();
+        let failed_inner = failed.clone();
+
         pool.spawn(lazy(move || {
             info!(target: "vm_event", "spawning root vm");
             // Run
-            ok({
-                vm_inner
-                    .run(first_mailbox, tx, id_inner, performance_inner, root_send)
-                    .unwrap();
-            })
+            if let Err(e) = vm_inner.run(first_mailbox, tx, id_inner, performance_inner, root_send)
+            {
+                warn!(target: "vm_event", "root vm failed {:?}", e);
+                failed_inner.store(true, Ordering::SeqCst);
+            }
+            ok(())
         }));
 
         // For each new message
@@ -172,11 +198,19 @@ impl Performance {
                         let receiver_id_inner = receiver_id.clone();
                         let vm_inner = VM::new(db.clone());
                         let inboxes_inner = inboxes_inner.clone();
+                        let failed_inner = failed.clone();
                         pool.spawn(lazy(move || {
                             info!(target: "vm_event", "spawning {:?} vm", receiver_id_inner);
-                            vm_inner
-                                .run(new_mailbox, tx, id_inner, performance_inner, parent_branch)
-                                .unwrap();
+                            if let Err(e) = vm_inner.run(
+                                new_mailbox,
+                                tx,
+                                id_inner,
+                                performance_inner,
+                                parent_branch,
+                            ) {
+                                warn!(target: "vm_event", "{:?} vm failed {:?}", receiver_id_inner, e);
+                                failed_inner.store(true, Ordering::SeqCst);
+                            }
                             // Remove from live inboxes
                             inboxes_inner.lock().unwrap().remove(&receiver_id);
 
@@ -187,12 +221,17 @@ impl Performance {
                 }
             })
             .join(root_recv.map(|_| drop(trigger)).map_err(|_| ()))
-            .map(move |_| match Arc::try_unwrap(performance_outer) {
-                Ok(some) => {
-                    info!(target: "vm_event", "performance complete");
-                    some.into_inner().unwrap()
+            .and_then(move |_| {
+                if failed_outer.load(Ordering::SeqCst) {
+                    return err(());
+                }
+                match Arc::try_unwrap(performance_outer) {
+                    Ok(some) => {
+                        info!(target: "vm_event", "performance complete");
+                        ok(some.into_inner().unwrap())
+                    }
+                    _ => unreachable!(),
                 }
-                _ => unreachable!(),
             })
     }
 
@@ -13,6 +13,8 @@ use log::info;
 use super::performance::Performance;
 use crate::{
     db::mongodb::MongoDB,
+    primitives::act::Event,
+    utils::constants::MAX_EVENTS_PER_ACT,
     vm::{Mailbox, Message},
 };
 
@@ -28,6 +30,7 @@ pub struct Session {
     pub performance: Arc<Mutex<Performance>>,
     pub child_branch: Option<oneshot::Receiver<()>>,
     pub store: MongoDB,
+    pub events: usize, // Events emitted so far
 }
 
 impl Session {
@@ -67,6 +70,17 @@ impl Session {
         );
     }
 
+    // Returns false once the act has emitted its maximum number of events
+    pub fn emit(&mut self, event: Event) -> bool {
+        info!(target: "vm_event", "emit syscall");
+        if self.events >= MAX_EVENTS_PER_ACT {
+            return false;
+        }
+        self.events += 1;
+        self.performance.lock().unwrap().add_event(&self.id, event);
+        true
+    }
+
     pub fn exit(&mut self) {
         info!(target: "vm_event", "exit syscall");
         // Wait while children still live
@@ -0,0 +1,193 @@
+use std::convert::TryInto;
+
+use bytes::Bytes;
+use failure::Error;
+use log::info;
+
+use crate::{
+    primitives::transaction::Transaction,
+    utils::{
+        constants::CONFIG,
+        errors::{BinaryValidationError, TimestampError},
+        timing::get_current_time,
+    },
+};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LE: u8 = 1;
+const ELF_TYPE_EXEC: u16 = 2;
+const ELF_MACHINE_RISCV: u16 = 243;
+const ELF_HEADER_LEN: usize = 64;
+const PROGRAM_HEADER_LEN: usize = 56;
+const SEGMENT_LOAD: u32 = 1;
+const SEGMENT_FLAG_EXEC: u32 = 1;
+
+fn read_u16(raw: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(raw[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(raw: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(raw[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(raw: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(raw[offset..offset + 8].try_into().unwrap())
+}
+
+struct Segment {
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+    memsz: u64,
+}
+
+impl Segment {
+    fn contains(&self, addr: u64) -> bool {
+        self.vaddr <= addr && addr < self.vaddr + self.memsz
+    }
+
+    fn is_executable(&self) -> bool {
+        self.flags & SEGMENT_FLAG_EXEC != 0
+    }
+}
+
+// Check a binary will load into the VM within the configured limits
+pub fn validate_binary(
+    binary: &Bytes,
+    max_binary_size: usize,
+    max_memory: u64,
+) -> Result<(), Error> {
+    if binary.len() > max_binary_size {
+        return Err(BinaryValidationError::TooLarge { size: binary.len() }.into());
+    }
+
+    // Check identification
+    if binary.len() < ELF_HEADER_LEN || binary[..4] != ELF_MAGIC {
+        return Err(BinaryValidationError::NotElf.into());
+    }
+    if binary[4] != ELF_CLASS_64 || binary[5] != ELF_DATA_LE {
+        return Err(BinaryValidationError::UnsupportedFormat.into());
+    }
+
+    // Check architecture and type
+    let machine = read_u16(binary, 18);
+    if machine != ELF_MACHINE_RISCV {
+        return Err(BinaryValidationError::WrongArchitecture { machine }.into());
+    }
+    if read_u16(binary, 16) != ELF_TYPE_EXEC {
+        return Err(BinaryValidationError::NotExecutable.into());
+    }
+
+    // Check program headers lie within the binary
+    let entry = read_u64(binary, 24);
+    let ph_offset = read_u64(binary, 32);
+    let ph_entry_len = usize::from(read_u16(binary, 54));
+    let ph_n = usize::from(read_u16(binary, 56));
+    if ph_entry_len != PROGRAM_HEADER_LEN {
+        return Err(BinaryValidationError::MalformedHeaders.into());
+    }
+    match ph_offset.checked_add((ph_n * PROGRAM_HEADER_LEN) as u64) {
+        Some(ph_end) if ph_end <= binary.len() as u64 => (),
+        _ => return Err(BinaryValidationError::MalformedHeaders.into()),
+    }
+
+    // Collect loadable segments
+    let ph_offset = ph_offset as usize;
+    let mut segments = Vec::with_capacity(ph_n);
+    for i in 0..ph_n {
+        let header = &binary[ph_offset + i * PROGRAM_HEADER_LEN..][..PROGRAM_HEADER_LEN];
+        if read_u32(header, 0) != SEGMENT_LOAD {
+            continue;
+        }
+        segments.push(Segment {
+            flags: read_u32(header, 4),
+            offset: read_u64(header, 8),
+            vaddr: read_u64(header, 16),
+            filesz: read_u64(header, 32),
+            memsz: read_u64(header, 40),
+        });
+    }
+    if segments.is_empty() {
+        return Err(BinaryValidationError::NoLoadableSegments.into());
+    }
+
+    // Check segments fit in the binary and in memory
+    for segment in &segments {
+        let file_end = segment.offset.checked_add(segment.filesz);
+        let mem_end = segment.vaddr.checked_add(segment.memsz);
+        match (file_end, mem_end) {
+            (Some(file_end), Some(mem_end)) => {
+                if file_end > binary.len() as u64 || segment.filesz > segment.memsz {
+                    return Err(BinaryValidationError::MalformedHeaders.into());
+                }
+                if mem_end > max_memory {
+                    return Err(BinaryValidationError::SegmentTooLarge.into());
+                }
+            }
+            _ => return Err(BinaryValidationError::MalformedHeaders.into()),
+        }
+    }
+
+    // Check entry point is executable
+    if !segments
+        .iter()
+        .any(|segment| segment.is_executable() && segment.contains(entry))
+    {
+        return Err(BinaryValidationError::InvalidEntryPoint { entry }.into());
+    }
+
+    Ok(())
+}
+
+// Admission check performed at RPC and network ingress
+pub fn validate_transaction(tx: &Transaction) -> Result<(), Error> {
+    let result = validate_binary(
+        &tx.get_binary(),
+        CONFIG.vm.max_binary_size,
+        CONFIG.vm.max_memory,
+    );
+    if let Err(err) = &result {
+        info!(target: "vm_event", "transaction rejected: {}", err);
+    }
+    result
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Timeliness {
+    Ready,
+    Deferred, // Admitted to the mempool until its time arrives
+}
+
+// Check a timestamp lies within the acceptance window around now
+pub fn check_timestamp(
+    time: u64,
+    now: u64,
+    max_past: u64,
+    max_future: u64,
+) -> Result<Timeliness, Error> {
+    if time < now.saturating_sub(max_past) {
+        Err(TimestampError::TooOld { time, now }.into())
+    } else if time > now.saturating_add(max_future) {
+        Err(TimestampError::TooFuture { time, now }.into())
+    } else if time > now {
+        Ok(Timeliness::Deferred)
+    } else {
+        Ok(Timeliness::Ready)
+    }
+}
+
+// Timestamp check for new transactions, reconciled history is exempt
+pub fn validate_timestamp(tx: &Transaction) -> Result<Timeliness, Error> {
+    let result = check_timestamp(
+        tx.get_time(),
+        get_current_time(),
+        CONFIG.admission.max_past_ms.as_millis() as u64,
+        CONFIG.admission.max_future_ms.as_millis() as u64,
+    );
+    if let Err(err) = &result {
+        info!(target: "vm_event", "transaction rejected: {}", err);
+    }
+    result
+}
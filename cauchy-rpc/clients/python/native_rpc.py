@@ -1,3 +1,4 @@
+import ipaddress
 import socket
 from transaction import Transaction
 
@@ -8,14 +9,16 @@ class NativeClient:
         self.socket = socket.socket()
 
     def connect(self):
-        self.socket.connect((self.ip, self.port))
+        # Resolves either address family
+        self.socket = socket.create_connection((self.ip, self.port))
 
     def close(self):
         self.socket.close()
 
     def add_peer(self, ip: str, port: int):
-        ip_bytes = bytes(map(int, ip.split(".")))
-        msg = b"\x00" + ip_bytes + int(port).to_bytes(2, byteorder="big")
+        # Version || IP || Port
+        addr = ipaddress.ip_address(ip)
+        msg = b"\x00" + bytes([addr.version]) + addr.packed + int(port).to_bytes(2, byteorder="big")
         self.socket.send(msg)
 
         if self.socket.recv(1) == b"\x01":
@@ -23,8 +26,9 @@ class NativeClient:
         elif self.socket.recv(1) != b"\x00":
             raise Exception("unexpected response")
 
-    def add_transaction(self, tx: Transaction):
-        msg = b"\x01" + tx.encode()
+    def add_transaction(self, tx: Transaction, force: bool = False):
+        # Forced transactions preempt an ongoing reconciliation
+        msg = b"\x01" + bytes([force]) + tx.encode()
         self.socket.send(msg)
 
         if self.socket.recv(1) == b"\x01":
@@ -47,3 +51,45 @@ class NativeClient:
         value = self.socket.recv(value_size)
 
         return value
+
+    def fetch_events(self, actor_id: bytes, topic: bytes = None, offset: int = 0, limit: int = 1024):
+        if topic is None:
+            msg = b"\x03" + actor_id + b"\x00"
+        else:
+            msg = b"\x03" + actor_id + b"\x01" + len(topic).to_bytes(4, "big") + topic
+        msg += offset.to_bytes(4, "big") + limit.to_bytes(4, "big")
+        self.socket.send(msg)
+
+        ret_val = self.socket.recv(1)
+
+        if ret_val == b"\x01":
+            raise Exception("failed to fetch events")
+        elif ret_val != b"\x04":
+            raise Exception("unexpected response")
+
+        n_events = int.from_bytes(self.socket.recv(4), "big")
+        events = []
+        for _ in range(n_events):
+            topic_size = int.from_bytes(self.socket.recv(4), "big")
+            event_topic = self.socket.recv(topic_size)
+            data_size = int.from_bytes(self.socket.recv(4), "big")
+            data = self.socket.recv(data_size)
+            events.append((event_topic, data))
+
+        return events
+
+    def fetch_mempool_size(self):
+        msg = b"\x04"
+        self.socket.send(msg)
+
+        ret_val = self.socket.recv(1)
+
+        if ret_val == b"\x01":
+            raise Exception("failed to fetch mempool size")
+        elif ret_val != b"\x05":
+            raise Exception("unexpected response")
+
+        size = int.from_bytes(self.socket.recv(4), "big")
+        capacity = int.from_bytes(self.socket.recv(4), "big")
+
+        return size, capacity
@@ -1,5 +1,6 @@
 import six
 from ecdsa import SigningKey, NIST256p
+import hashlib
 from hashlib import sha256
 
 def encode_varint(num: int):
@@ -44,4 +45,32 @@ def gen_contract_data():
     # print(hasher.hexdigest() + "E803000000000000")
     return (hasher.hexdigest() + "E803000000000000", pstr)
 
-print(gen_contract_data())
\ No newline at end of file
+print(gen_contract_data())
+
+ARG_U32 = 0
+ARG_U64 = 1
+ARG_BYTES = 2
+ARG_ID = 3
+
+
+def selector(method: str):
+    return hashlib.blake2b(method.encode()).digest()[:4]
+
+
+def encode_call(method: str, args: list):
+    """Encode a call as aux data, args is a list of (tag, value) pairs"""
+    parts = [selector(method), bytes([len(args)])]
+    for tag, value in args:
+        parts.append(bytes([tag]))
+        if tag == ARG_U32:
+            parts.append(value.to_bytes(4, byteorder="little"))
+        elif tag == ARG_U64:
+            parts.append(value.to_bytes(8, byteorder="little"))
+        elif tag == ARG_BYTES:
+            parts.append(len(value).to_bytes(4, byteorder="little") + value)
+        elif tag == ARG_ID:
+            assert len(value) == 32
+            parts.append(value)
+        else:
+            raise Exception("unknown argument tag")
+    return b''.join(parts)
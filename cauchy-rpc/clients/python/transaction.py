@@ -25,7 +25,7 @@ class Transaction:
             self.binary = binary
 
     def encode(self):
-        time_vi = encode_varint(int(time() * 1_000))
+        time_vi = encode_varint(self.timestamp)
         aux_len = encode_varint(len(self.aux))
         bin_len = encode_varint(len(self.binary))
         msg = time_vi + aux_len + self.aux + bin_len + self.binary
@@ -1,6 +1,7 @@
 pub mod native;
 
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 
 use bytes::Bytes;
 use futures::{sync::mpsc::Sender, Future};
@@ -9,13 +10,34 @@ use tokio::net::TcpStream;
 use core::{
     daemon::{Origin, Priority},
     db::mongodb::MongoDB,
-    primitives::{transaction::Transaction, tx_pool::TxPool},
+    primitives::{act::Event, transaction::Transaction, tx_pool::TxPool},
+    stage::InFlight,
 };
 
 pub enum Request {
-    AddPeer { addr: SocketAddr },               // 0 || Peer addr
-    NewTransaction { tx: Transaction },         // 1 || Transaction
-    FetchValue { actor_id: Bytes, key: Bytes }, // 2 || Actor ID || Key
+    // 0 || Versioned peer addr
+    AddPeer {
+        addr: SocketAddr,
+    },
+    // 1 || Force || Transaction
+    NewTransaction {
+        tx: Transaction,
+        force: bool,
+    },
+    // 2 || Actor ID || Key
+    FetchValue {
+        actor_id: Bytes,
+        key: Bytes,
+    },
+    // 3 || Actor ID || Has Topic || Topic || Offset || Limit
+    FetchEvents {
+        actor_id: Bytes,
+        topic: Option<Bytes>,
+        offset: usize,
+        limit: usize,
+    },
+    // 4
+    FetchMempoolSize,
 }
 
 pub enum Response {
@@ -23,17 +45,27 @@ pub enum Response {
     Error,
     NotFound,
     Value(Bytes),
+    Events(Vec<Event>),
+    MempoolSize { size: usize, capacity: usize },
 }
 
 pub fn construct_rpc_stack(
     socket_sender: Sender<TcpStream>,
     stage_send: Sender<(Origin, TxPool, Priority)>,
+    mempool: Arc<Mutex<TxPool>>,
+    in_flight: InFlight,
     db: MongoDB,
 ) -> Vec<Box<Future<Item = (), Error = ()> + Send + 'static>> {
     let mut stack: Vec<Box<Future<Item = (), Error = ()> + Send + 'static>> = Vec::new();
 
     #[cfg(feature = "native-rpc")]
-    stack.push(native::interface::server(socket_sender, stage_send, db));
+    stack.push(native::interface::server(
+        socket_sender,
+        stage_send,
+        mempool,
+        in_flight,
+        db,
+    ));
 
     stack
 }
@@ -1,4 +1,5 @@
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 
 use bson::{bson, doc, spec::BinarySubtype, Bson};
 use bytes::Bytes;
@@ -16,18 +17,85 @@ use super::messages::RPCCodec;
 use crate::{Request, Response};
 
 use core::{
+    crypto::hashes::Identifiable,
     daemon::{Origin, Priority},
-    db::{mongodb::MongoDB, DataType, Database},
-    primitives::tx_pool::TxPool,
+    db::{blocking, mongodb::MongoDB, storing::fetch_events, DataType, Database},
+    net::addresses::normalise,
+    primitives::{transaction::Transaction, tx_pool::TxPool},
+    stage::{is_stored, InFlight},
     utils::{constants::CONFIG, errors::RPCError},
+    vm::validation::{validate_timestamp, validate_transaction, Timeliness},
 };
 
+type ResponseFuture = Box<Future<Item = Response, Error = Error> + Send>;
+
+fn respond(response: Response) -> ResponseFuture {
+    Box::new(future::ok(response))
+}
+
+// Queue a transaction for the stage, or the mempool until its timestamp arrives
+fn submit_transaction(
+    tx: Transaction,
+    force: bool,
+    stage_send: Sender<(Origin, TxPool, Priority)>,
+    mempool: &Arc<Mutex<TxPool>>,
+    in_flight: InFlight,
+    socket_addr: SocketAddr,
+) -> Response {
+    let tx_id = tx.get_id();
+    let release = |in_flight: &InFlight| {
+        in_flight.lock().unwrap().remove(&tx_id);
+    };
+    match validate_timestamp(&tx) {
+        Ok(Timeliness::Ready) => (),
+        Ok(Timeliness::Deferred) => {
+            // Held in mempool until its timestamp arrives, in flight until stored
+            return match mempool.lock().unwrap().insert(tx, None, None) {
+                Ok(_) => Response::Success,
+                Err(_) => {
+                    release(&in_flight);
+                    Response::Error
+                }
+            };
+        }
+        Err(err) => {
+            error!(target: "rpc_event", "rejected transaction from {}: {}", socket_addr, err);
+            release(&in_flight);
+            return Response::Error;
+        }
+    }
+
+    // Preempting reconciliation is left to explicit requests
+    let priority = if force {
+        Priority::Force
+    } else {
+        Priority::Standard
+    };
+    let mut tx_pool = TxPool::with_capacity(1); // TODO: Make single insertion less clunky
+    tx_pool.insert(tx, None, None);
+    tokio::spawn(
+        stage_send
+            .send((Origin::RPC, tx_pool, priority))
+            .and_then(|_| future::ok(()))
+            .map(|_| ())
+            .or_else(move |e| {
+                error!("error = {:?}", e);
+                in_flight.lock().unwrap().remove(&tx_id);
+                Ok(())
+            }),
+    );
+    Response::Success
+}
+
 pub fn server(
     socket_sender: Sender<TcpStream>,
     stage_send: Sender<(Origin, TxPool, Priority)>,
+    mempool: Arc<Mutex<TxPool>>,
+    in_flight: InFlight,
     db: MongoDB,
 ) -> Box<Future<Item = (), Error = ()> + Send + 'static> {
-    let addr = format!("0.0.0.0:{}", CONFIG.network.rpc_server_port).to_string();
+    // Dual stack
+    let addr = format!("[::]:{}", CONFIG.network.rpc_server_port).to_string();
     let addr = addr.parse::<SocketAddr>().unwrap();
 
     let listener = TcpListener::bind(&addr)
@@ -40,7 +108,7 @@ pub fn server(
         .map_err(|e| error!("error accepting socket; error = {:?}", e));
 
     let server = incoming.for_each(move |socket| {
-        let socket_addr = socket.peer_addr().unwrap();
+        let socket_addr = normalise(socket.peer_addr().unwrap());
         info!(target: "rpc_event", "new rpc connection to {}", socket_addr);
 
         // Frame sockets
@@ -51,60 +119,117 @@ pub fn server(
         let socket_sender_inner = socket_sender.clone();
         let db_inner = db.clone();
         let stage_send_inner = stage_send.clone();
-        let responses = received_stream.map(move |msg| match msg {
-            Request::AddPeer { addr } => {
-                info!(target: "rpc_event", "received addpeer {} message from {}", addr, socket_addr);
-                let socket_sender_inner = socket_sender_inner.clone();
-                tokio::spawn(
-                    TcpStream::connect(&addr)
-                        .and_then(move |sock| {
-                            socket_sender_inner.send(sock).map_err(|_e| {
-                                std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    "rpc addpeer channel failure",
-                                )
+        let mempool_inner = mempool.clone();
+        let in_flight_inner = in_flight.clone();
+        let responses = received_stream.and_then(move |msg| -> ResponseFuture {
+            match msg {
+                Request::AddPeer { addr } => {
+                    info!(target: "rpc_event", "received addpeer {} message from {}", addr, socket_addr);
+                    let socket_sender_inner = socket_sender_inner.clone();
+                    tokio::spawn(
+                        TcpStream::connect(&addr)
+                            .and_then(move |sock| {
+                                socket_sender_inner.send(sock).map_err(|_e| {
+                                    std::io::Error::new(
+                                        std::io::ErrorKind::Other,
+                                        "rpc addpeer channel failure",
+                                    )
+                                })
+                            })
+                            .map(|_| ())
+                            .or_else(|e| {
+                                error!("error = {:?}", e);
+                                Ok(())
+                            }),
+                    );
+                    respond(Response::Success)
+                }
+                Request::NewTransaction { tx, force } => {
+                    info!(target: "rpc_event", "received new transaction from {}", socket_addr);
+                    if let Err(err) = validate_transaction(&tx) {
+                        error!(target: "rpc_event", "rejected transaction from {}: {}", socket_addr, err);
+                        return respond(Response::Error);
+                    }
+
+                    // Claim the id, rejecting replays of in-flight or stored transactions
+                    let tx_id = tx.get_id();
+                    if !in_flight_inner.lock().unwrap().insert(tx_id.clone()) {
+                        error!(target: "rpc_event", "rejected duplicate transaction from {}", socket_addr);
+                        return respond(Response::Error);
+                    }
+                    let db = db_inner.clone();
+                    let stage_send = stage_send_inner.clone();
+                    let mempool = mempool_inner.clone();
+                    let in_flight = in_flight_inner.clone();
+                    Box::new(
+                        blocking(move || (is_stored(&db, &tx), tx)).then(move |res| {
+                            Ok(match res {
+                                Ok((false, tx)) => submit_transaction(
+                                    tx,
+                                    force,
+                                    stage_send,
+                                    &mempool,
+                                    in_flight,
+                                    socket_addr,
+                                ),
+                                _ => {
+                                    error!(target: "rpc_event", "rejected duplicate transaction from {}", socket_addr);
+                                    in_flight.lock().unwrap().remove(&tx_id);
+                                    Response::Error
+                                }
                             })
-                        })
-                        .map(|_| ())
-                        .or_else(|e| {
-                            error!("error = {:?}", e);
-                            Ok(())
                         }),
-                );
-                Response::Success
-            }
-            Request::NewTransaction { tx } => {
-                info!(target: "rpc_event", "received new transaction from {}", socket_addr);
-                let stage_send_inner = stage_send_inner.clone();
-                let mut tx_pool = TxPool::with_capacity(1); // TODO: Make single insertion less clunky
-                tx_pool.insert(tx, None, None);
-                tokio::spawn(
-                    stage_send_inner
-                        .send((Origin::RPC, tx_pool, Priority::Standard))
-                        .and_then(|_| future::ok(()))
-                        .map(|_| ())
-                        .or_else(|e| {
-                            error!("error = {:?}", e);
-                            Ok(())
+                    )
+                }
+                Request::FetchValue { actor_id, key } => {
+                    let doc = doc! {
+                        "t" : Bson::Binary(BinarySubtype::Generic, actor_id.to_vec()),
+                        "$or" : [
+                            { "p" :  Bson::Null },
+                            { "p" : {"$exists" : false}},
+                        ],
+                        "k" : Bson::Binary(BinarySubtype::Generic, key.to_vec()),
+                    };
+                    let db = db_inner.clone();
+                    Box::new(
+                        blocking(move || db.get(&DataType::State, doc)).then(|res| {
+                            Ok(match res {
+                                Ok(Ok(Some(some))) => Response::Value(Bytes::from(
+                                    &some.get_binary_generic("v").unwrap()[..],
+                                )),
+                                Ok(Ok(None)) => Response::NotFound,
+                                _ => Response::Error,
+                            })
                         }),
-                );
-                Response::Success
-            }
-            Request::FetchValue { actor_id, key } => {
-                let doc = doc! {
-                    "t" : Bson::Binary(BinarySubtype::Generic, actor_id.to_vec()),
-                    "$or" : [
-                        { "p" :  Bson::Null },
-                        { "p" : {"$exists" : false}},
-                    ],
-                    "k" : Bson::Binary(BinarySubtype::Generic, key.to_vec()),
-                };
-                let result = match db_inner.get(&DataType::State, doc) {
-                    Ok(Some(some)) => Bytes::from(&some.get_binary_generic("v").unwrap()[..]),
-                    Ok(None) => return Response::NotFound,
-                    Err(_) => return Response::Error,
-                };
-                Response::Value(result)
+                    )
+                }
+                Request::FetchEvents {
+                    actor_id,
+                    topic,
+                    offset,
+                    limit,
+                } => {
+                    info!(target: "rpc_event", "received fetch events from {}", socket_addr);
+                    let db = db_inner.clone();
+                    Box::new(
+                        blocking(move || fetch_events(&db, actor_id, topic, offset, limit)).then(
+                            |res| {
+                                Ok(match res {
+                                    Ok(Ok(events)) => Response::Events(events),
+                                    _ => Response::Error,
+                                })
+                            },
+                        ),
+                    )
+                }
+                Request::FetchMempoolSize => {
+                    info!(target: "rpc_event", "received fetch mempool size from {}", socket_addr);
+                    let mempool_guard = mempool_inner.lock().unwrap();
+                    respond(Response::MempoolSize {
+                        size: mempool_guard.len(),
+                        capacity: mempool_guard.capacity(),
+                    })
+                }
             }
         });
         let send = send_stream
@@ -1,10 +1,9 @@
-use std::net::SocketAddr;
-
 use bytes::{Buf, BufMut, Bytes, BytesMut, IntoBuf};
 use failure::Error;
 use tokio::codec::{Decoder, Encoder};
 // use tokio::io::{Error, ErrorKind};
 
+use core::net::peers::Peer;
 use core::primitives::transaction::Transaction;
 use core::utils::constants::HASH_LEN;
 use core::utils::parsing::Parsable;
@@ -33,6 +32,26 @@ impl Encoder for RPCCodec {
                 dst.put_u32_be(val.len() as u32);
                 dst.extend(val);
             }
+            Response::Events(events) => {
+                dst.reserve(5);
+                dst.put_u8(4);
+                dst.put_u32_be(events.len() as u32);
+                for event in events {
+                    let topic = event.get_topic();
+                    let data = event.get_data();
+                    dst.reserve(8 + topic.len() + data.len());
+                    dst.put_u32_be(topic.len() as u32);
+                    dst.extend(topic);
+                    dst.put_u32_be(data.len() as u32);
+                    dst.extend(data);
+                }
+            }
+            Response::MempoolSize { size, capacity } => {
+                dst.reserve(9);
+                dst.put_u8(5);
+                dst.put_u32_be(size as u32);
+                dst.put_u32_be(capacity as u32);
+            }
         }
         Ok(())
     }
@@ -51,22 +70,27 @@ impl Decoder for RPCCodec {
         match buf.get_u8() {
             0 => {
                 // Add Peer
-                // TODO: Catch errors
-                let mut dst_ip = [0; 4];
-                buf.copy_to_slice(&mut dst_ip);
-                let dst_port = buf.get_u16_be();
-                let addr = SocketAddr::from((dst_ip, dst_port));
-                src.advance(7);
-                Ok(Some(Request::AddPeer { addr }))
+                let (peer, peer_len) = match Peer::parse_buf(&mut buf)? {
+                    Some(some) => some,
+                    None => return Ok(None),
+                };
+                src.advance(peer_len + 1);
+                Ok(Some(Request::AddPeer {
+                    addr: peer.get_addr(),
+                }))
             }
             1 => {
-                // Add transaction to own state
+                // Add transaction to own state, forced transactions preempt reconciliation
+                if buf.remaining() < 1 {
+                    return Ok(None);
+                }
+                let force = buf.get_u8() != 0;
                 let (tx, tx_len) = match Transaction::parse_buf(&mut buf)? {
                     Some(some) => some,
                     None => return Ok(None),
                 };
-                src.advance(tx_len + 1);
-                Ok(Some(Request::NewTransaction { tx }))
+                src.advance(tx_len + 2);
+                Ok(Some(Request::NewTransaction { tx, force }))
             }
             2 => {
                 // Add transaction to own state
@@ -82,6 +106,53 @@ impl Decoder for RPCCodec {
                     key: Bytes::from(&dst_key[..]),
                 }))
             }
+            3 => {
+                // Fetch events emitted by actor
+                if buf.remaining() < HASH_LEN + 1 {
+                    return Ok(None);
+                }
+                let mut dst_actor_id = [0; HASH_LEN];
+                buf.copy_to_slice(&mut dst_actor_id);
+
+                let topic = match buf.get_u8() {
+                    0 => None,
+                    _ => {
+                        if buf.remaining() < 4 {
+                            return Ok(None);
+                        }
+                        let topic_len = buf.get_u32_be() as usize;
+                        if buf.remaining() < topic_len {
+                            return Ok(None);
+                        }
+                        let mut dst_topic = vec![0; topic_len];
+                        buf.copy_to_slice(&mut dst_topic);
+                        Some(Bytes::from(dst_topic))
+                    }
+                };
+
+                if buf.remaining() < 8 {
+                    return Ok(None);
+                }
+                let offset = buf.get_u32_be() as usize;
+                let limit = buf.get_u32_be() as usize;
+
+                let topic_len = match &topic {
+                    Some(some) => 4 + some.len(),
+                    None => 0,
+                };
+                src.advance(1 + HASH_LEN + 1 + topic_len + 8);
+                Ok(Some(Request::FetchEvents {
+                    actor_id: Bytes::from(&dst_actor_id[..]),
+                    topic,
+                    offset,
+                    limit,
+                }))
+            }
+            4 => {
+                // Fetch mempool size
+                src.advance(1);
+                Ok(Some(Request::FetchMempoolSize))
+            }
             _ => unreachable!(),
         }
     }
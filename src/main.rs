@@ -1,32 +1,70 @@
 use bus::Bus;
 
 use core::{
-    crypto::signatures::ecdsa,
     daemon::{Origin, Priority},
-    db::{mongodb::MongoDB, *},
+    db::{mongodb::MongoDB, storing::load_addresses, *},
     ego::ego::Ego,
-    net::heartbeats::*,
+    net::{connections, heartbeats::*, relay},
     primitives::{arena::*, tx_pool::TxPool},
-    stage::Stage,
-    utils::{constants::*, logging::*, mining},
+    stage::{mempool, Stage},
+    utils::{constants::*, identity::*, logging::*, mining},
+    vm::ASM_AVAILABLE,
 };
 
 use futures::lazy;
 use futures::sync::mpsc;
+use log::warn;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+// Manage the node identity key
+fn key_command(cmd: Option<&str>) {
+    let path = identity_path().unwrap();
+    let res = match cmd {
+        Some("generate") => generate_identity(&path),
+        Some("show") => read_identity(&path).map(|keypair| match keypair {
+            Some((_, pk)) => pk,
+            None => {
+                eprintln!("no identity key at {}", path.display());
+                std::process::exit(1)
+            }
+        }),
+        Some("rotate") => rotate_identity(&path),
+        _ => {
+            eprintln!("usage: cauchy key <generate|show|rotate>");
+            std::process::exit(1)
+        }
+    };
+    match res {
+        Ok(pk) => println!("{}", pk),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1)
+        }
+    }
+}
+
 fn main() {
     // std::env::set_var("RUST_LOG", "info");
 
     // Enviroment logger
     log::set_logger(&CLogger).map(|()| log::set_max_level(log::LevelFilter::Info));
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("key") {
+        key_command(args.get(2).map(String::as_str));
+        return;
+    }
+
+    if CONFIG.vm.machine == MachineFlavour::Asm && !ASM_AVAILABLE {
+        warn!(target: "startup_event", "asm machine unavailable, falling back to sparse");
+    }
+
     // Init DB
     let db = MongoDB::open_db("cauchy").unwrap();
 
-    // Generate node key pair
-    let (local_sk, local_pk) = ecdsa::generate_keypair();
+    // Load node key pair, generated on first start
+    let (local_sk, local_pk) = load_identity(&identity_path().unwrap()).unwrap();
 
     // Construct distance pipeline
     let (distance_send, distance_recv) = std::sync::mpsc::channel();
@@ -58,14 +96,24 @@ fn main() {
     // Init Arena
     let arena = Arc::new(Mutex::new(Arena::new(ego.clone())));
 
+    // Load address book
+    match load_addresses(&db) {
+        Ok(records) => arena.lock().unwrap().load_addresses(records),
+        Err(err) => warn!(target: "startup_event", "failed to load address book: {}", err),
+    }
+
     // Init mempool
-    let mempool = Arc::new(Mutex::new(TxPool::with_capacity(1024))); // TODO: Make mempool size constant
+    let mempool = Arc::new(Mutex::new(TxPool::with_capacity(CONFIG.mempool.size)));
 
     // Spawn stage manager
     // let (reset_send, reset_recv) = std::sync::mpsc::channel(); // TODO: Reset mining best
     let (stage_send, stage_recv) = mpsc::channel::<(Origin, TxPool, Priority)>(128);
     let stage = Stage::new(ego.clone(), db.clone(), ego_bus);
+    let in_flight = stage.get_in_flight();
+    let stage_events = stage.get_events().subscribe();
     let stage_mananger = stage.manager(mempool.clone(), stage_recv);
+    let mempool_processor =
+        mempool::processor(ego.clone(), mempool.clone(), db.clone(), stage_send.clone());
 
     // Server
     let (socket_send, socket_recv) = mpsc::channel::<tokio::net::TcpStream>(128);
@@ -79,20 +127,39 @@ fn main() {
     );
 
     // Construct RPC server stack
-    let rpc_server_stack = rpc::construct_rpc_stack(socket_send, stage_send, db.clone());
+    let rpc_server_stack = rpc::construct_rpc_stack(
+        socket_send.clone(),
+        stage_send,
+        mempool.clone(),
+        in_flight,
+        db.clone(),
+    );
 
     // Reconciliation heartbeat
     let heartbeat_fut = heartbeat(arena.clone());
 
+    // Keepalive pings
+    let keepalive_fut = keepalive(arena.clone());
+
+    // Transaction relay
+    let relay_fut = relay::relay(arena.clone(), stage_events);
+
+    // Outbound connection manager
+    let connections_fut = connections::manager(arena.clone(), socket_send, db.clone());
+
     // Spawn servers
     let main_loop = thread::spawn(move || {
         tokio::run(lazy(|| {
             tokio::spawn(stage_mananger);
+            tokio::spawn(mempool_processor);
             tokio::spawn(server);
             for server in rpc_server_stack {
                 tokio::spawn(server);
             }
             tokio::spawn(heartbeat_fut);
+            tokio::spawn(keepalive_fut);
+            tokio::spawn(relay_fut);
+            tokio::spawn(connections_fut);
             Ok(())
         }))
     });